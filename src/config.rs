@@ -1,7 +1,19 @@
+use std::path::PathBuf;
+
 pub const DEFAULT_WIDTH: i32 = 800;
 pub const DEFAULT_HEIGHT: i32 = 800;
 pub const DEFAULT_URL: &str = "file://examples/welcome.html";
 
+pub const PROFILE_DIR_ENV: &str = "BOWSERNET_PROFILE";
+pub const COOKIES_FILE: &str = "cookies.txt";
+/// The directory in the profile where cached responses are kept between runs.
+pub const CACHE_DIR: &str = "cache";
+/// How big the files in the cache directory can get before the least recently used ones are
+/// deleted.
+pub const CACHE_MAX_SIZE: u64 = 50 * 1024 * 1024;
+/// Command line flag that empties the disk cache before loading the first page.
+pub const CLEAR_CACHE_FLAG: &str = "--clear-cache";
+
 pub const SCROLL_BAR_WIDTH: f32 = 10.;
 
 pub const FPS_WIDTH: f32 = 68.;
@@ -15,3 +27,13 @@ pub struct Dimensions {
     pub width: i32,
     pub height: i32,
 }
+
+/// Where the browser keeps data between runs, such as cookies. This is `$BOWSERNET_PROFILE` if
+/// it's set, or `~/.bowsernet` otherwise.
+pub fn profile_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(PROFILE_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    let home = std::env::var_os("HOME").map_or_else(|| PathBuf::from("."), PathBuf::from);
+    home.join(".bowsernet")
+}
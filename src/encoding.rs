@@ -0,0 +1,305 @@
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+
+use crate::mime::MediaType;
+
+/// How many bytes of a document to search for a `<meta charset>` declaration.
+const PRESCAN_LENGTH: usize = 1024;
+
+/// Works out the character encoding of a document, trying in order: a byte order mark, the
+/// `charset` parameter of its `Content-Type`, and a `<meta charset>` in the first 1024 bytes.
+/// Undeclared documents are assumed to be UTF-8 if they are valid UTF-8, and Windows-1252
+/// otherwise.
+pub fn detect_encoding(content_type: Option<&MediaType>, body: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(body) {
+        return encoding;
+    }
+
+    let declared = content_type
+        .and_then(|content_type| content_type.parameter("charset"))
+        .and_then(|charset| Encoding::for_label(charset.as_bytes()));
+    if let Some(encoding) = declared {
+        return encoding;
+    }
+
+    let is_html = content_type.is_none_or(|content_type| content_type.essence == "text/html");
+    if is_html {
+        if let Some(encoding) = prescan(&body[..body.len().min(PRESCAN_LENGTH)]) {
+            return encoding;
+        }
+    }
+
+    if std::str::from_utf8(body).is_ok() {
+        UTF_8
+    } else {
+        WINDOWS_1252
+    }
+}
+
+/// A simplified version of the HTML standard's "prescan a byte stream to determine its
+/// encoding" algorithm, which looks for the first `<meta>` tag declaring a charset.
+fn prescan(bytes: &[u8]) -> Option<&'static Encoding> {
+    let mut position = 0;
+    while position < bytes.len() {
+        let rest = &bytes[position..];
+        if rest.starts_with(b"<!--") {
+            position += find(&rest[2..], b"-->").map_or(rest.len(), |end| end + 5);
+        } else if starts_with_ignore_case(rest, b"<meta")
+            && rest.get(5).is_some_and(|&c| is_space(c) || c == b'/')
+        {
+            let (attributes, length) = parse_attributes(&rest[5..]);
+            position += 5 + length;
+            if let Some(encoding) = meta_encoding(&attributes) {
+                return Some(encoding);
+            }
+        } else if rest.starts_with(b"<") && rest.get(1).is_some_and(|c| c.is_ascii_alphabetic())
+            || rest.starts_with(b"</") && rest.get(2).is_some_and(|c| c.is_ascii_alphabetic())
+        {
+            // Skip over other tags, making sure a `>` inside an attribute value doesn't end them.
+            let name_length = rest
+                .iter()
+                .position(|&c| is_space(c) || c == b'>')
+                .unwrap_or(rest.len());
+            let (_, length) = parse_attributes(&rest[name_length..]);
+            position += name_length + length;
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            position += find(rest, b">").map_or(rest.len(), |end| end + 1);
+        } else {
+            position += 1;
+        }
+    }
+    None
+}
+
+fn meta_encoding(attributes: &[(String, String)]) -> Option<&'static Encoding> {
+    let attribute = |name: &str| {
+        attributes
+            .iter()
+            .find(|(attribute_name, _)| attribute_name == name)
+            .map(|(_, value)| value.as_str())
+    };
+
+    let label = if let Some(charset) = attribute("charset") {
+        charset.to_string()
+    } else if attribute("http-equiv")
+        .is_some_and(|value| value.eq_ignore_ascii_case("content-type"))
+    {
+        charset_from_content(attribute("content")?)?
+    } else {
+        return None;
+    };
+
+    let encoding = Encoding::for_label(label.trim_ascii().as_bytes())?;
+    // A document can't declare itself to be UTF-16 from inside itself, since the declaration
+    // would then not be readable as ASCII.
+    if encoding == encoding_rs::UTF_16BE || encoding == encoding_rs::UTF_16LE {
+        Some(UTF_8)
+    } else if encoding == encoding_rs::X_USER_DEFINED {
+        Some(WINDOWS_1252)
+    } else {
+        Some(encoding)
+    }
+}
+
+/// Extracts the charset from the `content` attribute of a `<meta http-equiv="Content-Type">`,
+/// e.g. `text/html; charset=iso-8859-1`.
+fn charset_from_content(content: &str) -> Option<String> {
+    let lowercase = content.to_ascii_lowercase();
+    let start = lowercase.find("charset")? + "charset".len();
+    let value = lowercase[start..].trim_ascii_start().strip_prefix('=')?;
+    let value = value.trim_ascii_start();
+    let value = match value.chars().next()? {
+        quote @ ('"' | '\'') => value[1..].split(quote).next()?,
+        _ => value.split([';', ' ', '\t', '\n', '\r']).next()?,
+    };
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Parses tag attributes up to and including the closing `>`, returning them with lowercased
+/// names along with the number of bytes consumed.
+fn parse_attributes(bytes: &[u8]) -> (Vec<(String, String)>, usize) {
+    let mut attributes = Vec::new();
+    let mut position = 0;
+    loop {
+        while bytes
+            .get(position)
+            .is_some_and(|&c| is_space(c) || c == b'/')
+        {
+            position += 1;
+        }
+        match bytes.get(position) {
+            None => return (attributes, position),
+            Some(b'>') => return (attributes, position + 1),
+            _ => {}
+        }
+
+        let name_start = position;
+        while bytes
+            .get(position)
+            .is_some_and(|&c| !is_space(c) && !b"/>=".contains(&c))
+        {
+            position += 1;
+        }
+        let name = String::from_utf8_lossy(&bytes[name_start..position]).to_ascii_lowercase();
+
+        while bytes.get(position).is_some_and(|&c| is_space(c)) {
+            position += 1;
+        }
+        let mut value = String::new();
+        if bytes.get(position) == Some(&b'=') {
+            position += 1;
+            while bytes.get(position).is_some_and(|&c| is_space(c)) {
+                position += 1;
+            }
+            let value_start;
+            match bytes.get(position) {
+                Some(&quote @ (b'"' | b'\'')) => {
+                    value_start = position + 1;
+                    position = value_start;
+                    while bytes.get(position).is_some_and(|&c| c != quote) {
+                        position += 1;
+                    }
+                    value = String::from_utf8_lossy(&bytes[value_start..position]).into_owned();
+                    position += 1;
+                }
+                _ => {
+                    value_start = position;
+                    while bytes
+                        .get(position)
+                        .is_some_and(|&c| !is_space(c) && c != b'>')
+                    {
+                        position += 1;
+                    }
+                    value = String::from_utf8_lossy(&bytes[value_start..position]).into_owned();
+                }
+            }
+        }
+
+        if !name.is_empty() && !attributes.iter().any(|(existing, _)| *existing == name) {
+            attributes.push((name, value));
+        }
+    }
+}
+
+fn is_space(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | b'\x0C' | b'\r')
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes
+        .get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{ISO_8859_2, SHIFT_JIS, UTF_16LE};
+
+    use super::*;
+
+    fn html() -> MediaType {
+        MediaType::new("text/html")
+    }
+
+    #[test]
+    fn bom_takes_priority() {
+        let content_type = html().with_parameter("charset", "iso-8859-2");
+        assert_eq!(
+            detect_encoding(Some(&content_type), b"\xEF\xBB\xBFHello"),
+            UTF_8
+        );
+        assert_eq!(
+            detect_encoding(Some(&content_type), b"\xFF\xFEH\x00i\x00"),
+            UTF_16LE
+        );
+    }
+
+    #[test]
+    fn content_type_charset() {
+        let content_type = html().with_parameter("charset", "Latin2");
+        assert_eq!(
+            detect_encoding(Some(&content_type), b"<meta charset=shift_jis>"),
+            ISO_8859_2
+        );
+    }
+
+    #[test]
+    fn unknown_content_type_charset_is_ignored() {
+        let content_type = html().with_parameter("charset", "klingon");
+        assert_eq!(
+            detect_encoding(Some(&content_type), b"<meta charset=shift_jis>"),
+            SHIFT_JIS
+        );
+    }
+
+    #[test]
+    fn meta_charset() {
+        for body in [
+            &b"<!doctype html><html><head><meta charset=\"windows-1252\">"[..],
+            b"<META CHARSET='cp1252'>",
+            b"<meta name=x charset=latin1 />",
+            b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=ISO-8859-1\">",
+            b"<meta content='text/html;charset=\"windows-1252\"' http-equiv=content-type>",
+        ] {
+            assert_eq!(
+                detect_encoding(Some(&html()), body),
+                WINDOWS_1252,
+                "{}",
+                String::from_utf8_lossy(body)
+            );
+        }
+    }
+
+    #[test]
+    fn meta_charset_ignored_in_comments_and_attributes() {
+        for body in [
+            &b"<!-- <meta charset=shift_jis> --><p>caf\xC3\xA9</p>"[..],
+            b"<p title='<meta charset=shift_jis>'>caf\xC3\xA9</p>",
+            b"<meta http-equiv=refresh charset-ish=shift_jis>",
+        ] {
+            assert_eq!(
+                detect_encoding(Some(&html()), body),
+                UTF_8,
+                "{}",
+                String::from_utf8_lossy(body)
+            );
+        }
+    }
+
+    #[test]
+    fn meta_charset_utf16_means_utf8() {
+        assert_eq!(
+            detect_encoding(Some(&html()), b"<meta charset=utf-16le>"),
+            UTF_8
+        );
+    }
+
+    #[test]
+    fn meta_charset_only_in_first_1024_bytes() {
+        let mut body = vec![b' '; PRESCAN_LENGTH];
+        body.extend_from_slice(b"<meta charset=shift_jis>");
+        assert_eq!(detect_encoding(Some(&html()), &body), UTF_8);
+    }
+
+    #[test]
+    fn meta_charset_only_in_html() {
+        assert_eq!(
+            detect_encoding(
+                Some(&MediaType::new("text/plain")),
+                b"<meta charset=shift_jis>"
+            ),
+            UTF_8
+        );
+    }
+
+    #[test]
+    fn undeclared_encoding() {
+        assert_eq!(detect_encoding(None, "café".as_bytes()), UTF_8);
+        assert_eq!(detect_encoding(None, b"caf\xE9"), WINDOWS_1252);
+    }
+}
@@ -4,6 +4,13 @@ pub enum Token {
     Text(String),
 }
 
+/// Escapes text so that it is displayed as-is when lexed as HTML.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
 pub fn lex(body: &str) -> Vec<Token> {
     let mut out = Vec::new();
     let mut in_tag = false;
@@ -1,223 +1,488 @@
-use color_eyre::eyre::OptionExt;
-use flate2::bufread::GzDecoder;
 use std::{
     fs::File,
-    io::{BufRead, Read, Write},
+    io::{BufRead, ErrorKind, Read},
 };
 
 use crate::{
-    cache::RequestCache,
-    http::headers::{CacheControl, Headers},
+    http::{
+        connection_pool::ConnectionKey,
+        content_encoding::decode_content,
+        parser::{body_length, read_body, read_response_head, BodyLength, ParseError},
+    },
+    mime::MediaType,
+    scheme::RequestContext,
     url::{BuiltinUrl, DataUrl, FileUrl, HttpUrl, Scheme},
     Url,
 };
 
+pub(crate) mod about;
 mod connection_pool;
+mod content_encoding;
+mod cookie_jar;
+pub(crate) mod date;
+mod error;
 mod headers;
+mod parser;
+mod request;
+mod response;
+#[cfg(test)]
+pub(crate) mod testing;
 
 pub use connection_pool::ConnectionPool;
+pub(crate) use cookie_jar::{create_private_dir, write_private_file};
+pub use cookie_jar::{Cookie, CookieJar, SameSite};
+pub use error::{NetworkError, NetworkErrorKind};
+pub(crate) use headers::CacheControl;
+pub use headers::Headers;
+pub use request::{Method, Request};
+pub use response::Response;
 
 const HTTP_VERSION: &str = "1.1";
 const USER_AGENT: &str = "bowsernet 0.00001";
 const REDIRECT_LIMIT: usize = 5;
 
-pub fn request(
-    url: &Url,
-    connection_pool: &mut ConnectionPool,
-    cache: &mut RequestCache,
-) -> color_eyre::Result<String> {
+pub fn request(url: &Url, context: &mut RequestContext) -> color_eyre::Result<Response> {
     tracing::info!("Requesting {}", url);
-    let content = match &url.scheme {
-        Scheme::Http(http_url) => handle_normal_request(http_url, connection_pool, cache, 0)?,
-        Scheme::File(file_url) => handle_file_request(file_url)?,
-        Scheme::Data(data_url) => handle_data_request(data_url)?,
-        Scheme::Builtin(builtin_url) => handle_builtin_request(builtin_url)?,
-    };
-    if url.view_source {
-        Ok(content
-            .replace("&", "&amp;")
-            .replace("<", "&lt;")
-            .replace(">", "&gt;"))
-    } else {
-        Ok(content)
-    }
+    let scheme = url.scheme.name();
+    let handler = context
+        .schemes
+        .get(scheme)
+        .ok_or_else(|| color_eyre::eyre::eyre!("No handler registered for scheme '{scheme}'"))?;
+    let mut response = handler.request(url, context)?;
+    response.url.view_source = url.view_source;
+    Ok(response)
+}
+
+/// Sends an HTTP request through the connection pool, following redirects. Only `GET`
+/// requests are served from and stored in the cache, and stale cached responses with an `ETag`
+/// or `Last-Modified` are revalidated with a conditional request. A stale response is also used
+/// when the server can't be reached, unless it has to be revalidated. Cookies from the cookie
+/// jar are attached to each request unless it already has a `Cookie` header, and cookies set by
+/// every response (including redirects) are stored in the jar. A request with a header that
+/// can't be sent as it is fails before anything is written.
+pub fn send(request: &Request, context: &mut RequestContext) -> color_eyre::Result<Response> {
+    request.check_headers()?;
+    handle_normal_request(request, context, 0)
 }
 
-#[tracing::instrument(skip(http_url, connection_pool, cache), fields(http_url = %http_url))]
+#[tracing::instrument(skip(request, context), fields(method = %request.method, http_url = %request.url))]
 fn handle_normal_request(
-    http_url: &HttpUrl,
-    connection_pool: &mut ConnectionPool,
-    cache: &mut RequestCache,
+    request: &Request,
+    context: &mut RequestContext,
     num_redirects: usize,
-) -> color_eyre::Result<String> {
+) -> color_eyre::Result<Response> {
     if num_redirects >= REDIRECT_LIMIT {
-        return Err(color_eyre::eyre::eyre!("Too many redirects"));
+        return Err(server_error(
+            request,
+            NetworkErrorKind::InvalidRedirect,
+            "Too many redirects",
+        ));
     }
 
-    if let Some(content) = cache.get(http_url) {
-        tracing::info!("Loading response from cache");
-        return Ok(content.to_string());
+    let http_url = &request.url;
+    let mut sent_request = request.clone();
+    if !request.headers.contains("cookie") {
+        if let Some(cookies) = context.cookie_jar.cookie_header(http_url) {
+            sent_request = sent_request.header("Cookie", &cookies);
+        }
     }
 
-    let stream = connection_pool.get_connection(http_url)?;
+    let cacheable = request.method == Method::Get;
+    if cacheable {
+        if let Some(response) = context.cache.get(&sent_request) {
+            tracing::info!("Loading response from cache");
+            let response = from_cache(response, http_url);
+            return follow_redirect(request, response, context, num_redirects);
+        }
+    }
 
-    let request_headers = Headers::new()
-        .add("Host", &http_url.host)
-        .add("Accept-Encoding", "gzip")
-        .add("User-Agent", USER_AGENT);
+    // A stale cached response can be revalidated, so the server only has to send the body again
+    // if it changed. Requests that are already conditional are left alone, since the caller
+    // wants to see the server's answer.
+    let is_conditional =
+        request.headers.contains("if-none-match") || request.headers.contains("if-modified-since");
+    let conditional_request = match context.cache.get_entry(&sent_request) {
+        Some(entry) if cacheable && !is_conditional && entry.has_validators() => {
+            tracing::info!("Revalidating stale cached response");
+            let mut revalidation = sent_request.clone();
+            if let Some(etag) = entry.etag() {
+                revalidation = revalidation.header("If-None-Match", etag);
+            }
+            if let Some(last_modified) = entry.last_modified() {
+                revalidation = revalidation.header("If-Modified-Since", last_modified);
+            }
+            Some(revalidation)
+        }
+        _ => None,
+    };
+    let revalidating = conditional_request.is_some();
 
-    write!(
-        stream.get_mut(),
-        "GET {} HTTP/{}\r\n",
-        http_url.path,
-        HTTP_VERSION
-    )?;
-    write!(stream.get_mut(), "{}\r\n", request_headers.to_http_string())?;
-    stream.get_mut().flush()?;
-
-    let mut line = String::new();
-    stream.read_line(&mut line)?;
-
-    let mut statusline = line.trim_ascii().splitn(3, ' ');
-    let _version = statusline
-        .next()
-        .ok_or_eyre("Version expected in HTTP response")?;
-    let status: u16 = statusline
-        .next()
-        .ok_or_eyre("Status expected in HTTP response")?
-        .parse()
-        .unwrap();
-    let explanation = statusline
-        .next()
-        .ok_or_eyre("Explanation expected in HTTP response")?;
-    tracing::info!("Server returned {} {}", status, explanation);
-
-    let mut response_headers = Headers::new();
-    loop {
-        line.clear();
-        stream.read_line(&mut line)?;
-        if line == "\r\n" {
-            break;
+    let mut response = match fetch(
+        conditional_request.as_ref().unwrap_or(&sent_request),
+        context,
+    ) {
+        Ok(response) => response,
+        Err(error) => {
+            // RFC 9111 lets a cache use a stale response when the server can't be reached,
+            // unless the response said it must always be revalidated.
+            let stale = context
+                .cache
+                .get_entry(&sent_request)
+                .filter(|entry| cacheable && entry.can_serve_stale() && error.is::<NetworkError>())
+                .map(|entry| from_cache(entry.response(), http_url));
+            let Some(response) = stale else {
+                return Err(error);
+            };
+            tracing::warn!("Using stale cached response: {error}");
+            return follow_redirect(request, response, context, num_redirects);
         }
-        let (header, value) = line
-            .trim()
-            .split_once(':')
-            .ok_or_eyre("Expected a colon in HTTP header line")?;
-        response_headers.set(header.trim(), value.trim());
-    }
-    tracing::debug!("Response headers: {:?}", &response_headers);
-
-    let content = if let Some(transfer_encoding) = response_headers.get("transfer-encoding") {
-        if transfer_encoding != "chunked" {
-            return Err(color_eyre::eyre::eyre!(
-                "Unhandled transfer-encoding: {transfer_encoding}"
-            ));
+    };
+    context
+        .cookie_jar
+        .store_response_cookies(http_url, &response.headers);
+
+    if let Some(content_encoding) = response.headers.get_combined("content-encoding") {
+        if !response.body.is_empty() {
+            response.body = decode_content(&content_encoding, response.body)
+                .map_err(|error| server_error(request, NetworkErrorKind::InvalidResponse, error))?;
+            // The body is used and cached decoded, so the headers need to describe it that way.
+            response.headers.remove("content-encoding");
+            if response.headers.contains("content-length") {
+                response
+                    .headers
+                    .set("Content-Length", &response.body.len().to_string());
+            }
         }
+    }
 
-        tracing::info!("Reading chunked response");
+    if revalidating && response.status == 304 {
+        if let Some(entry) = context.cache.get_entry(&sent_request) {
+            tracing::info!("Cached response is still valid");
+            let mut cached = from_cache(entry.response(), http_url);
+            update_cached_headers(&mut cached.headers, &response.headers);
+            context.cache.set(&sent_request, &cached);
+            return follow_redirect(request, cached, context, num_redirects);
+        }
+    }
 
-        let mut expected_newline = vec![0; 2];
-        let mut content = Vec::new();
-        let mut line = String::new();
-        let mut chunk = Vec::new();
-        loop {
-            line.clear();
-            stream.read_line(&mut line)?;
-            let chunk_length = usize::from_str_radix(line.trim_ascii_end(), 16)?;
+    if cacheable {
+        context.cache.set(&sent_request, &response);
+    }
+    follow_redirect(request, response, context, num_redirects)
+}
 
-            tracing::debug!("Reading chunk of length {chunk_length}");
+/// Sends a request and reads the response, turning any failure to talk to the server into a
+/// [`NetworkError`].
+fn fetch(request: &Request, context: &mut RequestContext) -> color_eyre::Result<Response> {
+    let http_url = &request.url;
+    let stream = context.connection_pool.send_request(
+        http_url,
+        &request.to_http_bytes(),
+        request.method.is_idempotent(),
+    )?;
+    let (response, keep_alive) = match read_response(stream, request) {
+        Ok(result) => result,
+        Err(error) => {
+            // There's no telling where the next response would start, so the connection can't
+            // be used again.
+            context.connection_pool.remove_connection(http_url);
+            let server = ConnectionKey::from(http_url).to_string();
+            return Err(match error.downcast::<ParseError>() {
+                Ok(ParseError::Io(error)) => NetworkError::from_io(&server, error).into(),
+                Ok(error) => {
+                    NetworkError::new(NetworkErrorKind::InvalidResponse, &server, error).into()
+                }
+                Err(error) => error,
+            });
+        }
+    };
+    if !keep_alive {
+        context.connection_pool.remove_connection(http_url);
+    }
+    Ok(response)
+}
 
-            chunk.clear();
-            chunk.resize(chunk_length, 0);
-            stream.read_exact(&mut chunk)?;
-            content.append(&mut chunk);
+/// A [`NetworkError`] for a server that sent something we can't use, so it's shown as an error
+/// page like any other problem with the server.
+fn server_error(
+    request: &Request,
+    kind: NetworkErrorKind,
+    message: impl std::fmt::Display,
+) -> color_eyre::Report {
+    let server = ConnectionKey::from(&request.url).to_string();
+    NetworkError::new(kind, &server, message).into()
+}
 
-            expected_newline.fill(0);
-            stream.read_exact(&mut expected_newline)?;
-            assert_eq!(expected_newline, b"\r\n");
+/// Request headers describing the body, which are dropped when a redirect turns a request into
+/// a `GET`.
+const REQUEST_BODY_HEADERS: [&str; 5] = [
+    "content-length",
+    "content-type",
+    "content-encoding",
+    "content-language",
+    "content-location",
+];
 
-            if chunk_length == 0 {
-                break;
-            }
-        }
+/// Request headers that aren't sent on when a redirect leads to a different origin.
+const CREDENTIAL_HEADERS: [&str; 3] = ["authorization", "proxy-authorization", "cookie"];
 
-        content
-    } else {
-        let content_length: usize = response_headers.get("content-length").unwrap().parse()?;
-        let mut content = vec![0; content_length];
-        stream.read_exact(&mut content)?;
-        content
+/// Follows the response if it's a redirect, or otherwise returns it as it is.
+fn follow_redirect(
+    request: &Request,
+    response: Response,
+    context: &mut RequestContext,
+    num_redirects: usize,
+) -> color_eyre::Result<Response> {
+    let status = response.status;
+    // 304 Not Modified is a 3xx status, but it answers a conditional request rather than
+    // pointing somewhere else. Other 3xx responses without a `Location` (like a 300 Multiple
+    // Choices page) are shown as they are.
+    let location = response
+        .headers
+        .get("location")
+        .filter(|_| (300..=399).contains(&status) && status != 304);
+    let Some(location) = location else {
+        return Ok(response);
     };
 
-    let content = if response_headers.contains("content-encoding") {
-        tracing::info!("Decompressing gzipped response");
-        let mut gz = GzDecoder::new(&content[..]);
-        let mut decompressed = String::new();
-        gz.read_to_string(&mut decompressed)?;
-        decompressed
-    } else {
-        String::from_utf8(content)?
+    tracing::info!("Redirecting to {}", location);
+    let redirect_url = response
+        .url
+        .resolve_with(location, context.schemes)
+        .map_err(|error| {
+            server_error(
+                request,
+                NetworkErrorKind::InvalidRedirect,
+                format!("Invalid Location '{location}': {error}"),
+            )
+        })?;
+    let is_same_origin = redirect_url.origin().same_origin(&response.url.origin());
+    let redirect_url = match redirect_url.scheme {
+        Scheme::Http(http_url) => http_url,
+        // Custom schemes are added by whoever embeds the browser, so a redirect to one is
+        // handed to its handler. Following a server's redirect to a local file, though, could
+        // leak the file to the server.
+        Scheme::Custom(_) => return self::request(&redirect_url, context),
+        _ => {
+            return Err(server_error(
+                request,
+                NetworkErrorKind::InvalidRedirect,
+                "Invalid redirect URL",
+            ))
+        }
     };
+    // 307 and 308 redirects repeat the request as it was, but browsers turn anything else
+    // into a GET without a body (leaving HEAD requests alone). The caller's other headers are
+    // sent either way.
+    let mut redirect = request.clone().with_url(redirect_url);
+    if status != 307 && status != 308 {
+        if redirect.method != Method::Head {
+            redirect.method = Method::Get;
+        }
+        redirect.body.clear();
+        for name in REQUEST_BODY_HEADERS {
+            redirect.headers.remove(name);
+        }
+    }
+    redirect.headers.set("Host", &redirect.url.authority());
+    // Credentials were only meant for the original server. Cookies for the new one still come
+    // from the cookie jar.
+    if !is_same_origin {
+        for name in CREDENTIAL_HEADERS {
+            redirect.headers.remove(name);
+        }
+    }
+    handle_normal_request(&redirect, context, num_redirects + 1)
+}
 
-    if (300..=399).contains(&status) {
-        let location = response_headers.get("location").unwrap();
-        tracing::info!("Redirecting to {}", location);
-        let redirect_url = if location.starts_with('/') {
-            HttpUrl {
-                path: location.to_string(),
-                ..http_url.clone()
-            }
-        } else if let Scheme::Http(redirect_url) = Url::parse(location)?.scheme {
-            redirect_url
-        } else {
-            return Err(color_eyre::eyre::eyre!("Invalid redirect URL"));
-        };
-        return handle_normal_request(&redirect_url, connection_pool, cache, num_redirects + 1);
+/// A copy of a cached response for a request to `http_url`. The cache leaves out the URL's
+/// username, password and fragment, so the URL is taken from the request instead.
+fn from_cache(cached: &Response, http_url: &HttpUrl) -> Response {
+    Response {
+        url: Url {
+            scheme: Scheme::Http(http_url.clone()),
+            view_source: false,
+        },
+        ..cached.clone()
     }
+}
 
-    if status == 200 {
-        let cache_control: CacheControl = response_headers
-            .get("cache-control")
-            .map(|value| value.into())
-            .unwrap_or_default();
+/// Updates a cached response's headers with the ones from a `304 Not Modified` response, as
+/// RFC 9111 section 4.3.4 describes. Headers describing the body are kept as they were, since
+/// the 304 doesn't have one.
+fn update_cached_headers(cached: &mut Headers, not_modified: &Headers) {
+    const BODY_HEADERS: [&str; 4] = [
+        "content-length",
+        "content-encoding",
+        "transfer-encoding",
+        "content-range",
+    ];
+    let updated: Vec<_> = not_modified
+        .iter()
+        .filter(|(name, _)| {
+            !BODY_HEADERS
+                .iter()
+                .any(|body| name.eq_ignore_ascii_case(body))
+        })
+        .collect();
+    for (name, _) in &updated {
+        cached.remove(name);
+    }
+    for (name, value) in updated {
+        cached.append(name, value);
+    }
+}
 
-        if cache_control.no_store {
-            tracing::info!("Not caching request due to no-store directive");
-        } else {
-            tracing::info!(
-                "Caching request with max_age of {:?}",
-                cache_control.max_age
-            );
-            cache.set(http_url, &content, cache_control.max_age);
+/// Reads a response's status line, headers and body from a connection, along with whether the
+/// connection can be kept open for another request.
+fn read_response(
+    stream: &mut impl BufRead,
+    request: &Request,
+) -> color_eyre::Result<(Response, bool)> {
+    let head = loop {
+        let head = read_response_head(stream)?;
+        // Interim responses like `100 Continue` come before the real response. (`101 Switching
+        // Protocols` is only sent when the client asks for it, which we never do.)
+        if (100..=199).contains(&head.status) && head.status != 101 {
+            tracing::info!("Skipping interim response {}", head.status);
+            continue;
         }
+        break head;
+    };
+    tracing::info!("Server returned {} {}", head.status, head.reason);
+    tracing::debug!("Response headers: {:?}", &head.headers);
+
+    let body_length = body_length(request.method, head.status, &head.headers)?;
+    match body_length {
+        BodyLength::Chunked => tracing::info!("Reading chunked response"),
+        BodyLength::UntilClose => tracing::info!("Reading response until the connection closes"),
+        BodyLength::Empty | BodyLength::Fixed(_) => {}
     }
+    let content = read_body(stream, &body_length)?;
 
-    Ok(content)
+    // Transfer codings other than `chunked` (e.g. `gzip, chunked`) work like content codings,
+    // except that they're applied to the message rather than the document.
+    let content = match head.headers.get_combined("transfer-encoding") {
+        Some(transfer_encoding) if !content.is_empty() => {
+            let codings = transfer_encoding.trim_ascii_end();
+            let codings = match codings.rsplit_once(',') {
+                Some((rest, last)) if last.trim_ascii().eq_ignore_ascii_case("chunked") => rest,
+                None if codings.eq_ignore_ascii_case("chunked") => "",
+                _ => codings,
+            };
+            decode_content(codings, content)
+                .map_err(|error| server_error(request, NetworkErrorKind::InvalidResponse, error))?
+        }
+        _ => content,
+    };
+
+    // A response with both `Transfer-Encoding` and `Content-Length` might be an attempt at
+    // request smuggling, so the connection isn't trusted afterwards.
+    let has_conflicting_lengths =
+        head.headers.contains("transfer-encoding") && head.headers.contains("content-length");
+    let keep_alive = body_length != BodyLength::UntilClose
+        && !has_conflicting_lengths
+        && is_keep_alive(&head.version, &request.headers, &head.headers);
+    let response = Response {
+        url: Url {
+            scheme: Scheme::Http(request.url.clone()),
+            view_source: false,
+        },
+        status: head.status,
+        reason: head.reason,
+        headers: head.headers,
+        body: content,
+    };
+    Ok((response, keep_alive))
 }
 
-fn handle_file_request(file_url: &FileUrl) -> color_eyre::Result<String> {
-    let mut f = File::open(&file_url.path)?;
-    let mut content = String::new();
-    f.read_to_string(&mut content)?;
-    Ok(content)
+/// Whether the connection can be reused after this response. HTTP/1.1 connections stay open
+/// unless either side sends `Connection: close`, while HTTP/1.0 connections only stay open if
+/// the server sends `Connection: keep-alive`.
+fn is_keep_alive(version: &str, request_headers: &Headers, response_headers: &Headers) -> bool {
+    let has_token = |headers: &Headers, token: &str| {
+        headers.get_combined("connection").is_some_and(|value| {
+            value
+                .split(',')
+                .any(|option| option.trim_ascii().eq_ignore_ascii_case(token))
+        })
+    };
+    if has_token(request_headers, "close") || has_token(response_headers, "close") {
+        false
+    } else if version == "HTTP/1.0" {
+        has_token(response_headers, "keep-alive")
+    } else {
+        true
+    }
 }
 
-fn handle_data_request(data_url: &DataUrl) -> color_eyre::Result<String> {
-    Ok(data_url.contents.clone())
+pub(crate) fn handle_file_request(url: &Url, file_url: &FileUrl) -> color_eyre::Result<Response> {
+    let mut f = match File::open(file_url.to_file_path()) {
+        Ok(f) => f,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Response::not_found(url)),
+        Err(error) => return Err(error.into()),
+    };
+    let mut content = Vec::new();
+    f.read_to_end(&mut content)?;
+    Ok(Response::ok(url, &guess_media_type(file_url), content))
 }
 
-fn handle_builtin_request(builtin_url: &BuiltinUrl) -> color_eyre::Result<String> {
-    match builtin_url {
-        BuiltinUrl::AboutBlank => Ok("".to_string()),
+/// Guesses the media type of a file from its extension, since the filesystem doesn't give us
+/// a `Content-Type`.
+fn guess_media_type(file_url: &FileUrl) -> MediaType {
+    let extension = file_url
+        .to_file_path()
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("html" | "htm") => MediaType::new("text/html"),
+        _ => MediaType::new("text/plain"),
     }
 }
 
+pub(crate) fn handle_data_request(url: &Url, data_url: &DataUrl) -> color_eyre::Result<Response> {
+    Ok(Response::ok(
+        url,
+        &data_url.media_type,
+        data_url.data.clone(),
+    ))
+}
+
+pub(crate) fn handle_builtin_request(
+    url: &Url,
+    builtin_url: &BuiltinUrl,
+    context: &RequestContext,
+) -> color_eyre::Result<Response> {
+    let html = match builtin_url {
+        BuiltinUrl::Blank => "".to_string(),
+        BuiltinUrl::Version => about::version_page(),
+        BuiltinUrl::Cache => about::cache_page(context),
+        BuiltinUrl::Connections => about::connections_page(context),
+        BuiltinUrl::History => about::history_page(context),
+        BuiltinUrl::Config => about::config_page(),
+    };
+    Ok(Response::ok(
+        url,
+        &MediaType::new("text/html").with_parameter("charset", "utf-8"),
+        html.into_bytes(),
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::http::connection_pool::fake::FakeStream;
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        cache::RequestCache,
+        http::{
+            connection_pool::fake::{BlockingStream, FakeStream},
+            testing::{http_url, request_context},
+        },
+        scheme::SchemeRegistry,
+    };
 
-    fn mocked_request(url: &Url, raw_response: &[u8]) -> color_eyre::Result<String> {
+    fn mocked_request(url: &Url, raw_response: &[u8]) -> color_eyre::Result<Response> {
         let http_url = match &url.scheme {
             Scheme::Http(http_url) => http_url,
             _ => return Err(color_eyre::eyre::eyre!("Mock URL's scheme must be HTTP(S)")),
@@ -226,7 +491,14 @@ mod tests {
         let mut connection_pool = ConnectionPool::new();
         connection_pool.set_connection(http_url, Box::new(FakeStream::new(raw_response)));
 
-        request(url, &mut connection_pool, &mut RequestCache::new())
+        request(
+            url,
+            &mut request_context(
+                &mut connection_pool,
+                &mut RequestCache::new(),
+                &mut CookieJar::new(),
+            ),
+        )
     }
 
     #[test]
@@ -240,7 +512,28 @@ mod tests {
 
         let response = mocked_request(&url, raw_response)?;
 
-        assert_eq!(response, "Hello, world!");
+        assert_eq!(response.text(), "Hello, world!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_data_url() -> color_eyre::Result<()> {
+        let cases = [
+            "data:text/html,%3Cp%3EHello%2C%20world!%3C/p%3E",
+            "data:text/html;base64,PHA+SGVsbG8sIHdvcmxkITwvcD4=",
+        ];
+        for input in cases {
+            let response = request(
+                &Url::parse(input)?,
+                &mut request_context(
+                    &mut ConnectionPool::new(),
+                    &mut RequestCache::new(),
+                    &mut CookieJar::new(),
+                ),
+            )?;
+            assert_eq!(response.text(), "<p>Hello, world!</p>");
+        }
 
         Ok(())
     }
@@ -256,7 +549,84 @@ mod tests {
 
         let response = mocked_request(&url, raw_response)?;
 
-        assert_eq!(response, "&lt;p&gt;Hello, world!&lt;/p&gt;");
+        assert!(response.url.view_source);
+        assert_eq!(response.text(), "<p>Hello, world!</p>");
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_keeps_status_headers_and_binary_body() -> color_eyre::Result<()> {
+        let url = Url::parse("http://example.org/missing.png")?;
+        let raw_response = b"\
+            HTTP/1.1 404 Not Found\r\n\
+            Content-Type: image/png\r\n\
+            Content-Length: 4\r\n\
+            \r\n\
+            \x89PNG";
+
+        let response = mocked_request(&url, raw_response)?;
+
+        assert_eq!(response.status, 404);
+        assert_eq!(response.reason, "Not Found");
+        assert!(response.is_error());
+        assert_eq!(response.content_type().unwrap().essence, "image/png");
+        assert_eq!(response.body, b"\x89PNG");
+        assert_eq!(response.url, url);
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_redirect_reports_final_url() -> color_eyre::Result<()> {
+        let url = Url::parse("http://example.org/old")?;
+        let raw_response = b"\
+            HTTP/1.1 301 Moved Permanently\r\n\
+            Location: /new\r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+            HTTP/1.1 200 OK\r\n\
+            Content-Length: 0\r\n\
+            \r\n";
+
+        let response = mocked_request(&url, raw_response)?;
+
+        assert_eq!(response.url.to_string(), "http://example.org/new");
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_missing_file_is_not_found() -> color_eyre::Result<()> {
+        let url = Url::parse("file:///nonexistent/bowsernet/page.html")?;
+        let response = request(
+            &url,
+            &mut request_context(
+                &mut ConnectionPool::new(),
+                &mut RequestCache::new(),
+                &mut CookieJar::new(),
+            ),
+        )?;
+
+        assert_eq!(response.status, 404);
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_file_guesses_media_type() -> color_eyre::Result<()> {
+        let url = Url::parse("file://examples/welcome.html")?;
+        let response = request(
+            &url,
+            &mut request_context(
+                &mut ConnectionPool::new(),
+                &mut RequestCache::new(),
+                &mut CookieJar::new(),
+            ),
+        )?;
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.content_type().unwrap().essence, "text/html");
 
         Ok(())
     }
@@ -276,7 +646,54 @@ mod tests {
 
         let response = mocked_request(&url, raw_response)?;
 
-        assert_eq!(response, "Hello, world!");
+        assert_eq!(response.text(), "Hello, world!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_with_relative_redirect() -> color_eyre::Result<()> {
+        let url = Url::parse("http://example.org/docs/index.html")?;
+        let raw_response = b"\
+            HTTP/1.1 302 Found\r\n\
+            Location: ../next.html\r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+            HTTP/1.1 200 OK\r\n\
+            Content-Length: 13\r\n\
+            \r\n\
+            Hello, world!";
+
+        let response = mocked_request(&url, raw_response)?;
+
+        assert_eq!(response.text(), "Hello, world!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_cache_ignores_fragment() -> color_eyre::Result<()> {
+        let url = Url::parse("http://example.org/#intro")?;
+        let Scheme::Http(http_url) = &url.scheme else {
+            unreachable!();
+        };
+        let raw_response = b"\
+            HTTP/1.1 200 OK\r\n\
+            Cache-Control: max-age=60\r\n\
+            Content-Length: 13\r\n\
+            \r\n\
+            Hello, world!";
+
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(http_url, Box::new(FakeStream::new(raw_response)));
+        let mut cache = RequestCache::new();
+
+        let mut cookie_jar = CookieJar::new();
+        let mut context = request_context(&mut connection_pool, &mut cache, &mut cookie_jar);
+        request(&url, &mut context)?;
+        let response = request(&Url::parse("http://example.org/#outro")?, &mut context)?;
+
+        assert_eq!(response.text(), "Hello, world!");
 
         Ok(())
     }
@@ -297,7 +714,7 @@ mod tests {
 
         let response = mocked_request(&url, raw_response)?;
 
-        assert_eq!(response, "Hello, world!");
+        assert_eq!(response.text(), "Hello, world!");
 
         Ok(())
     }
@@ -317,7 +734,1002 @@ mod tests {
 
         let response = mocked_request(&url, raw_response)?;
 
-        assert_eq!(response, "Hello, world!");
+        assert_eq!(response.text(), "Hello, world!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_stacked_content_encodings() -> color_eyre::Result<()> {
+        let url = Url::parse("http://example.org")?;
+        let raw_response = b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Encoding: gzip\r\n\
+            Content-Encoding: br\r\n\
+            Content-Length: 37\r\n\
+            \r\n\
+            \x0B\x10\x80\x1F\x8B\x08\x00\x00\x00\x00\x00\x00\x03\xF3\x48\xCD\xC9\xC9\xD7\x51\x28\xCF\x2F\xCA\x49\x51\x04\x00\xE6\xC6\xE6\xEB\x0D\x00\x00\x00\x03";
+
+        let response = mocked_request(&url, raw_response)?;
+
+        assert_eq!(response.text(), "Hello, world!");
+        assert!(!response.headers.contains("content-encoding"));
+        assert_eq!(response.headers.get("content-length"), Some("13"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_zstd() -> color_eyre::Result<()> {
+        let url = Url::parse("http://example.org")?;
+        let raw_response = b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Encoding: zstd\r\n\
+            Content-Length: 26\r\n\
+            \r\n\
+            \x28\xB5\x2F\xFD\x04\x68\x69\x00\x00\x48\x65\x6C\x6C\x6F\x2C\x20\x77\x6F\x72\x6C\x64\x21\x76\x94\x6F\x8B";
+
+        let response = mocked_request(&url, raw_response)?;
+
+        assert_eq!(response.text(), "Hello, world!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_unknown_content_encoding() -> color_eyre::Result<()> {
+        let url = Url::parse("http://example.org")?;
+        let raw_response = b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Encoding: compress\r\n\
+            Content-Length: 13\r\n\
+            \r\n\
+            Hello, world!";
+
+        let error = mocked_request(&url, raw_response)
+            .unwrap_err()
+            .downcast::<NetworkError>()?;
+
+        assert_eq!(error.kind, NetworkErrorKind::InvalidResponse);
+        assert_eq!(error.message, "Unsupported content-encoding: compress");
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_head_request_with_content_encoding() -> color_eyre::Result<()> {
+        let request = Request::head(&http_url("http://example.org/"));
+        let raw_response = b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Encoding: gzip\r\n\
+            Content-Length: 33\r\n\
+            \r\n";
+
+        let (response, written) = mocked_send(&request, raw_response, &mut RequestCache::new())?;
+
+        assert!(response.body.is_empty());
+        assert!(written.contains("Accept-Encoding: gzip, deflate, br, zstd\r\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_about_pages() -> color_eyre::Result<()> {
+        let url = Url::parse("http://example.org/<index>.html")?;
+        let Scheme::Http(http_url) = &url.scheme else {
+            unreachable!();
+        };
+        let raw_response = b"\
+            HTTP/1.1 200 OK\r\n\
+            Cache-Control: max-age=60\r\n\
+            Content-Length: 13\r\n\
+            \r\n\
+            Hello, world!";
+
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(http_url, Box::new(FakeStream::new(raw_response)));
+        let mut cache = RequestCache::new();
+        let history = [url.clone()];
+        let mut cookie_jar = CookieJar::new();
+        let mut context = RequestContext {
+            history: &history,
+            ..request_context(&mut connection_pool, &mut cache, &mut cookie_jar)
+        };
+        request(&url, &mut context)?;
+
+        let mut about = |page: &str| {
+            request(&Url::parse(&format!("about:{page}"))?, &mut context)
+                .map(|response| response.text())
+        };
+
+        let version = about("version")?;
+        assert!(version.contains(env!("CARGO_PKG_VERSION")));
+        assert!(version.contains(USER_AGENT));
+
+        let cache_page = about("cache")?;
+        assert!(cache_page.contains("1 entries"));
+        assert!(cache_page.contains("http://example.org/%3Cindex%3E.html"));
+        assert!(cache_page.contains("13 bytes, age 0s, max age 60s, fresh"));
+
+        let connections = about("connections")?;
+        assert!(connections.contains("1 open connections"));
+        assert!(connections.contains("http://example.org:80"));
+
+        let history = about("history")?;
+        assert!(history.contains("1 pages visited"));
+        assert!(history.contains("http://example.org/%3Cindex%3E.html"));
+
+        let config = about("config")?;
+        assert!(config.contains(crate::config::DEFAULT_URL));
+
+        assert_eq!(about("blank")?, "");
+
+        Ok(())
+    }
+
+    fn mocked_send(
+        request: &Request,
+        raw_response: &[u8],
+        cache: &mut RequestCache,
+    ) -> color_eyre::Result<(Response, String)> {
+        let stream = FakeStream::new(raw_response);
+        let written = stream.written();
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(&request.url, Box::new(stream));
+
+        let response = send(
+            request,
+            &mut request_context(&mut connection_pool, cache, &mut CookieJar::new()),
+        )?;
+        let written = String::from_utf8(written.borrow().clone())?;
+        Ok((response, written))
+    }
+
+    #[test]
+    fn send_post_request() -> color_eyre::Result<()> {
+        let request = Request::post(&http_url("http://example.org/form"))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("q=hello");
+        let raw_response = b"\
+            HTTP/1.1 200 OK\r\n\
+            Cache-Control: max-age=60\r\n\
+            Content-Length: 6\r\n\
+            \r\n\
+            Posted";
+
+        let mut cache = RequestCache::new();
+        let (response, written) = mocked_send(&request, raw_response, &mut cache)?;
+
+        assert_eq!(response.text(), "Posted");
+        assert!(written.starts_with("POST /form HTTP/1.1\r\n"));
+        assert!(written.contains("Content-Length: 7\r\n"));
+        assert!(written.ends_with("\r\n\r\nq=hello"));
+        assert!(cache.get(&request).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_head_request_ignores_content_length() -> color_eyre::Result<()> {
+        let request = Request::head(&http_url("http://example.org/"));
+        let raw_response = b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Length: 13\r\n\
+            \r\n";
+
+        let (response, written) = mocked_send(&request, raw_response, &mut RequestCache::new())?;
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.headers.get("content-length"), Some("13"));
+        assert!(response.body.is_empty());
+        assert!(written.starts_with("HEAD / HTTP/1.1\r\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_unfollowable_redirect_is_a_network_error() -> color_eyre::Result<()> {
+        let url = Url::parse("http://example.org/loop")?;
+        let redirect = "HTTP/1.1 302 Found\r\nLocation: /loop\r\nContent-Length: 0\r\n\r\n";
+        for (raw_response, message) in [
+            (
+                "HTTP/1.1 302 Found\r\nLocation: http://[bad\r\n\r\n".to_string(),
+                "Invalid Location 'http://[bad': Invalid host '[bad'",
+            ),
+            (redirect.repeat(REDIRECT_LIMIT), "Too many redirects"),
+        ] {
+            let error = mocked_request(&url, raw_response.as_bytes())
+                .expect_err("unfollowable redirect")
+                .downcast::<NetworkError>()
+                .expect("network error");
+            assert_eq!(error.kind, NetworkErrorKind::InvalidRedirect);
+            assert_eq!(error.message, message);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_post_redirect_becomes_get() -> color_eyre::Result<()> {
+        let request = Request::post(&http_url("http://example.org/form")).body("q=hello");
+        let raw_response = b"\
+            HTTP/1.1 303 See Other\r\n\
+            Location: /done\r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+            HTTP/1.1 200 OK\r\n\
+            Content-Length: 4\r\n\
+            \r\n\
+            Done";
+
+        let (response, written) = mocked_send(&request, raw_response, &mut RequestCache::new())?;
+
+        assert_eq!(response.text(), "Done");
+        let (_, redirected) = written.split_once("q=hello").unwrap();
+        assert!(redirected.starts_with("GET /done HTTP/1.1\r\n"));
+        assert!(!redirected.contains("Content-Length"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_put_307_redirect_keeps_method_and_body() -> color_eyre::Result<()> {
+        let request = Request::put(&http_url("http://example.org/item")).body("data");
+        let raw_response = b"\
+            HTTP/1.1 307 Temporary Redirect\r\n\
+            Location: /item2\r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+            HTTP/1.1 204 No Content\r\n\
+            Content-Length: 0\r\n\
+            \r\n";
+
+        let (response, written) = mocked_send(&request, raw_response, &mut RequestCache::new())?;
+
+        assert_eq!(response.status, 204);
+        let (_, redirected) = written.split_once("data").unwrap();
+        assert!(redirected.starts_with("PUT /item2 HTTP/1.1\r\n"));
+        assert!(redirected.ends_with("\r\n\r\ndata"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_redirect_keeps_caller_headers() -> color_eyre::Result<()> {
+        let request = Request::post(&http_url("http://example.org/form"))
+            .header("Accept-Language", "fr")
+            .header("Authorization", "Bearer secret")
+            .header("Content-Type", "text/plain")
+            .body("q=hello");
+        let raw_response = b"\
+            HTTP/1.1 302 Found\r\n\
+            Location: /done\r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+            HTTP/1.1 200 OK\r\n\
+            Content-Length: 0\r\n\
+            \r\n";
+
+        let (_, written) = mocked_send(&request, raw_response, &mut RequestCache::new())?;
+
+        let (_, redirected) = written.split_once("q=hello").unwrap();
+        assert!(redirected.starts_with("GET /done HTTP/1.1\r\n"));
+        assert!(redirected.contains("Accept-Language: fr\r\n"));
+        assert!(redirected.contains("Authorization: Bearer secret\r\n"));
+        assert!(!redirected.contains("Content-Type"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_cross_origin_redirect_drops_credentials() -> color_eyre::Result<()> {
+        for status in ["302 Found", "307 Temporary Redirect"] {
+            let request = Request::get(&http_url("http://example.org/"))
+                .header("Accept-Language", "fr")
+                .header("Authorization", "Bearer secret")
+                .header("Proxy-Authorization", "Basic c2VjcmV0")
+                .header("Cookie", "session=abc123");
+            let first = FakeStream::new(
+                format!(
+                    "HTTP/1.1 {status}\r\n\
+                     Location: https://other.example/\r\n\
+                     Content-Length: 0\r\n\r\n"
+                )
+                .as_bytes(),
+            );
+            let second = FakeStream::new(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+            let written = second.written();
+            let mut connection_pool = ConnectionPool::new();
+            connection_pool.set_connection(&request.url, Box::new(first));
+            connection_pool.set_connection(&http_url("https://other.example/"), Box::new(second));
+
+            send_with_pool(&request, &mut connection_pool)?;
+
+            let written = String::from_utf8(written.borrow().clone())?;
+            assert!(written.contains("Host: other.example\r\n"), "{status}");
+            assert!(written.contains("Accept-Language: fr\r\n"), "{status}");
+            assert!(!written.contains("Authorization"), "{status}");
+            assert!(!written.contains("Cookie"), "{status}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn request_redirect_to_custom_scheme() -> color_eyre::Result<()> {
+        struct DocsHandler;
+        impl crate::SchemeHandler for DocsHandler {
+            fn request(
+                &self,
+                url: &Url,
+                _context: &mut RequestContext,
+            ) -> color_eyre::Result<Response> {
+                Ok(Response::ok(
+                    url,
+                    &MediaType::new("text/plain"),
+                    b"Docs".to_vec(),
+                ))
+            }
+        }
+        let mut schemes = SchemeRegistry::new();
+        schemes.register("docs", DocsHandler);
+
+        for (location, expected) in [
+            ("docs://guide/intro.html", Ok("docs://guide/intro.html")),
+            ("file:///etc/passwd", Err("Invalid redirect URL")),
+        ] {
+            let url = Url::parse("http://example.org/help")?;
+            let Scheme::Http(http_url) = &url.scheme else {
+                unreachable!();
+            };
+            let mut connection_pool = ConnectionPool::new();
+            connection_pool.set_connection(
+                http_url,
+                Box::new(FakeStream::new(
+                    format!("HTTP/1.1 302 Found\r\nLocation: {location}\r\n\r\n").as_bytes(),
+                )),
+            );
+            let result = request(
+                &url,
+                &mut RequestContext {
+                    schemes: &schemes,
+                    ..request_context(
+                        &mut connection_pool,
+                        &mut RequestCache::new(),
+                        &mut CookieJar::new(),
+                    )
+                },
+            );
+            match (result, expected) {
+                (Ok(response), Ok(expected)) => {
+                    assert_eq!(response.url.to_string(), expected);
+                    assert_eq!(response.text(), "Docs");
+                }
+                (Err(error), Err(expected)) => {
+                    let error = error.downcast::<NetworkError>()?;
+                    assert_eq!(error.kind, NetworkErrorKind::InvalidRedirect);
+                    assert_eq!(error.message, expected);
+                }
+                (result, _) => panic!("{location}: {:?}", result.map(|r| r.url)),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_stores_and_sends_cookies() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/login"));
+        let raw_response = b"\
+            HTTP/1.1 302 Found\r\n\
+            Location: /account\r\n\
+            Set-Cookie: session=abc123; Path=/; HttpOnly\r\n\
+            Set-Cookie: theme=dark; Path=/; Max-Age=3600\r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+            HTTP/1.1 200 OK\r\n\
+            Content-Length: 7\r\n\
+            \r\n\
+            Welcome";
+
+        let stream = FakeStream::new(raw_response);
+        let written = stream.written();
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(&request.url, Box::new(stream));
+        let mut cookie_jar = CookieJar::new();
+
+        let response = send(
+            &request,
+            &mut request_context(
+                &mut connection_pool,
+                &mut RequestCache::new(),
+                &mut cookie_jar,
+            ),
+        )?;
+
+        assert_eq!(response.text(), "Welcome");
+        assert_eq!(cookie_jar.cookies().len(), 2);
+        let written = String::from_utf8(written.borrow().clone())?;
+        let (first, redirected) = written.split_once("GET /account").unwrap();
+        assert!(!first.contains("Cookie"));
+        assert!(redirected.contains("Cookie: session=abc123; theme=dark\r\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_keeps_explicit_cookie_header() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/")).header("Cookie", "mine=1");
+        let raw_response = b"\
+            HTTP/1.1 200 OK\r\n\
+            Set-Cookie: theirs=2\r\n\
+            Content-Length: 0\r\n\
+            \r\n";
+
+        let (_, written) = mocked_send(&request, raw_response, &mut RequestCache::new())?;
+
+        assert!(written.contains("Cookie: mine=1\r\n"));
+        assert!(!written.contains("theirs"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_connection_close_removes_connection() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut connection_pool = ConnectionPool::new();
+        for (raw_response, open) in [
+            (&b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"[..], 1),
+            (
+                b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+                0,
+            ),
+            (b"HTTP/1.0 200 OK\r\nContent-Length: 0\r\n\r\n", 0),
+            (
+                b"HTTP/1.0 200 OK\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n",
+                1,
+            ),
+        ] {
+            connection_pool.set_connection(&request.url, Box::new(FakeStream::new(raw_response)));
+            send(
+                &request,
+                &mut request_context(
+                    &mut connection_pool,
+                    &mut RequestCache::new(),
+                    &mut CookieJar::new(),
+                ),
+            )?;
+            assert_eq!(connection_pool.connections().len(), open);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_retries_get_on_closed_connection() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(
+            &request.url,
+            Box::new(FakeStream::new(
+                b"HTTP/1.1 200 OK\r\nCache-Control: no-store\r\nContent-Length: 5\r\n\r\nfirst",
+            )),
+        );
+        connection_pool.queue_connection(Box::new(FakeStream::new(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\nsecond",
+        )));
+        let mut cache = RequestCache::new();
+        let mut cookie_jar = CookieJar::new();
+        let mut context = request_context(&mut connection_pool, &mut cache, &mut cookie_jar);
+
+        assert_eq!(send(&request, &mut context)?.text(), "first");
+        assert_eq!(send(&request, &mut context)?.text(), "second");
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_reads_body_until_close() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(
+            &request.url,
+            Box::new(FakeStream::new(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nNo length given",
+            )),
+        );
+
+        let response = send_with_pool(&request, &mut connection_pool)?;
+        assert_eq!(response.text(), "No length given");
+        assert!(connection_pool.connections().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_responses_without_body() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(
+            &request.url,
+            Box::new(FakeStream::new(
+                b"HTTP/1.1 204 No Content\r\n\r\n\
+                  HTTP/1.1 304 Not Modified\r\nContent-Length: 100\r\n\r\n\
+                  HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nlast",
+            )),
+        );
+
+        let response = send_with_pool(&request, &mut connection_pool)?;
+        assert_eq!(response.status, 204);
+        assert!(response.body.is_empty());
+        let response = send_with_pool(&request, &mut connection_pool)?;
+        assert_eq!(response.status, 304);
+        assert!(response.body.is_empty());
+        let response = send_with_pool(&request, &mut connection_pool)?;
+        assert_eq!(response.text(), "last");
+        assert_eq!(connection_pool.connections().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_skips_interim_responses() -> color_eyre::Result<()> {
+        let request = Request::post(&http_url("http://example.org/form")).body("q=hello");
+        let (response, _) = mocked_send(
+            &request,
+            b"HTTP/1.1 100 Continue\r\n\r\n\
+              HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n\
+              HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nThanks",
+            &mut RequestCache::new(),
+        )?;
+
+        assert_eq!(response.status, 200);
+        assert_eq!(response.text(), "Thank");
+        assert!(!response.headers.contains("Link"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_decodes_transfer_codings() -> color_eyre::Result<()> {
+        let gzip = b"\x1F\x8B\x08\x00\x00\x00\x00\x00\x00\x03\xF3\x48\xCD\xC9\xC9\xD7\x51\x28\xCF\x2F\xCA\x49\x51\x04\x00\xE6\xC6\xE6\xEB\x0D\x00\x00\x00";
+        let mut raw_response =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, chunked\r\n\r\n21\r\n".to_vec();
+        raw_response.extend_from_slice(gzip);
+        raw_response.extend_from_slice(b"\r\n0\r\n\r\n");
+
+        let request = Request::get(&http_url("http://example.org/"));
+        let (response, _) = mocked_send(&request, &raw_response, &mut RequestCache::new())?;
+        assert_eq!(response.text(), "Hello, world!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_conflicting_lengths_closes_connection() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(
+            &request.url,
+            Box::new(FakeStream::new(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\nTransfer-Encoding: chunked\r\n\r\n\
+                  2\r\nOK\r\n0\r\n\r\n",
+            )),
+        );
+
+        assert_eq!(send_with_pool(&request, &mut connection_pool)?.text(), "OK");
+        assert!(connection_pool.connections().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_redirect_status_without_location() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/"));
+        let (response, _) = mocked_send(
+            &request,
+            b"HTTP/1.1 300 Multiple Choices\r\nContent-Length: 7\r\n\r\nChoose!",
+            &mut RequestCache::new(),
+        )?;
+        assert_eq!(response.status, 300);
+        assert_eq!(response.text(), "Choose!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_malformed_response_is_a_network_error() {
+        let request = Request::get(&http_url("http://example.org/"));
+        for (raw_response, message) in [
+            (
+                &b"HTTP/1.1 200 OK\r\nContent-Length: 5, 6\r\n\r\nHello!"[..],
+                "Invalid Content-Length: 5, 6",
+            ),
+            (b"HTTP/1.1 OK\r\n\r\n", "Invalid status code 'OK'"),
+            (
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nHello!\r\n",
+                "Chunk data not followed by CRLF",
+            ),
+        ] {
+            let mut connection_pool = ConnectionPool::new();
+            connection_pool.set_connection(&request.url, Box::new(FakeStream::new(raw_response)));
+            let error = send_with_pool(&request, &mut connection_pool)
+                .expect_err("malformed response")
+                .downcast::<NetworkError>()
+                .expect("network error");
+            assert_eq!(error.kind, NetworkErrorKind::InvalidResponse);
+            assert_eq!(error.message, message);
+            assert!(connection_pool.connections().is_empty());
+        }
+    }
+
+    #[test]
+    fn send_undecodable_body_is_a_network_error() {
+        let request = Request::get(&http_url("http://example.org/"));
+        for (raw_response, message) in [
+            (
+                &b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 5\r\n\r\nHello"[..],
+                "unexpected end of file",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, chunked\r\n\r\n5\r\nHello\r\n0\r\n\r\n",
+                "unexpected end of file",
+            ),
+        ] {
+            let mut connection_pool = ConnectionPool::new();
+            connection_pool.set_connection(&request.url, Box::new(FakeStream::new(raw_response)));
+            let error = send_with_pool(&request, &mut connection_pool)
+                .expect_err("undecodable body")
+                .downcast::<NetworkError>()
+                .expect("network error");
+            assert_eq!(error.kind, NetworkErrorKind::InvalidResponse);
+            assert_eq!(error.message, message);
+        }
+    }
+
+    #[test]
+    fn send_truncated_response_is_a_closed_connection() {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(
+            &request.url,
+            Box::new(FakeStream::new(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\nHello",
+            )),
+        );
+        let error = send_with_pool(&request, &mut connection_pool)
+            .expect_err("truncated response")
+            .downcast::<NetworkError>()
+            .expect("network error");
+        assert_eq!(error.kind, NetworkErrorKind::ConnectionClosed);
+    }
+
+    /// Caches a response with validators that's immediately stale.
+    fn stale_cache(request: &Request) -> color_eyre::Result<RequestCache> {
+        let mut cache = RequestCache::new();
+        mocked_send(
+            request,
+            b"HTTP/1.1 200 OK\r\n\
+              ETag: \"v1\"\r\n\
+              Last-Modified: Wed, 21 Oct 2015 07:28:00 GMT\r\n\
+              Cache-Control: max-age=0\r\n\
+              Content-Type: text/plain\r\n\
+              Content-Length: 8\r\n\r\n\
+              Original",
+            &mut cache,
+        )?;
+        assert!(cache.get(request).is_none());
+        Ok(cache)
+    }
+
+    #[test]
+    fn send_revalidates_stale_response() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut cache = stale_cache(&request)?;
+
+        let (response, written) = mocked_send(
+            &request,
+            b"HTTP/1.1 304 Not Modified\r\n\
+              ETag: \"v1\"\r\n\
+              Cache-Control: max-age=60\r\n\
+              Content-Length: 100\r\n\r\n",
+            &mut cache,
+        )?;
+
+        assert!(written.contains("If-None-Match: \"v1\"\r\n"));
+        assert!(written.contains("If-Modified-Since: Wed, 21 Oct 2015 07:28:00 GMT\r\n"));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.text(), "Original");
+        assert_eq!(response.headers.get("cache-control"), Some("max-age=60"));
+        assert_eq!(response.headers.get("content-length"), Some("8"));
+        assert_eq!(response.headers.get("content-type"), Some("text/plain"));
+
+        let entry = cache.get_entry(&request).expect("cached");
+        assert!(!entry.is_stale());
+        assert_eq!(entry.max_age(), Duration::from_secs(60));
+        assert_eq!(
+            cache.get(&request).map(|response| response.text()),
+            Some("Original".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_revalidation_replaces_changed_response() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut cache = stale_cache(&request)?;
+
+        let (response, written) = mocked_send(
+            &request,
+            b"HTTP/1.1 200 OK\r\nETag: \"v2\"\r\nContent-Length: 7\r\n\r\nUpdated",
+            &mut cache,
+        )?;
+
+        assert!(written.contains("If-None-Match: \"v1\"\r\n"));
+        assert_eq!(response.text(), "Updated");
+        let entry = cache.get_entry(&request).expect("cached");
+        assert_eq!(entry.etag(), Some("\"v2\""));
+        assert_eq!(entry.last_modified(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_leaves_conditional_requests_alone() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/")).header("If-None-Match", "*");
+        let mut cache = stale_cache(&request)?;
+
+        let (response, written) =
+            mocked_send(&request, b"HTTP/1.1 304 Not Modified\r\n\r\n", &mut cache)?;
+
+        assert!(written.contains("If-None-Match: *\r\n"));
+        assert!(!written.contains("If-Modified-Since"));
+        assert_eq!(response.status, 304);
+        assert!(cache.get(&request).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_without_validators_is_unconditional() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut cache = RequestCache::new();
+        mocked_send(
+            &request,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\nOriginal",
+            &mut cache,
+        )?;
+        // There's no point keeping a stale response that can't be revalidated.
+        assert!(cache.get_entry(&request).is_none());
+
+        let (response, written) = mocked_send(
+            &request,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\nUpdated",
+            &mut cache,
+        )?;
+
+        assert!(!written.contains("If-"));
+        assert_eq!(response.text(), "Updated");
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_caches_permanent_redirects() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/old"));
+        let mut connection_pool = ConnectionPool::new();
+        let stream = FakeStream::new(
+            b"HTTP/1.1 301 Moved Permanently\r\nLocation: /new\r\nContent-Length: 0\r\n\r\n\
+              HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nContent-Length: 3\r\n\r\nNew",
+        );
+        let written = stream.written();
+        connection_pool.set_connection(&request.url, Box::new(stream));
+        let mut cache = RequestCache::new();
+        let mut cookie_jar = CookieJar::new();
+        let mut context = request_context(&mut connection_pool, &mut cache, &mut cookie_jar);
+
+        assert_eq!(send(&request, &mut context)?.text(), "New");
+        assert_eq!(send(&request, &mut context)?.text(), "New");
+        let written = String::from_utf8(written.borrow().clone())?;
+        assert_eq!(written.matches("GET /old ").count(), 1);
+        assert_eq!(written.matches("GET /new ").count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_caches_not_found() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/missing"));
+        let mut cache = RequestCache::new();
+        let (response, _) = mocked_send(
+            &request,
+            b"HTTP/1.1 404 Not Found\r\nCache-Control: max-age=60\r\nContent-Length: 4\r\n\r\nGone",
+            &mut cache,
+        )?;
+
+        assert_eq!(response.status, 404);
+        assert_eq!(
+            cache.get(&request).map(|response| response.status),
+            Some(404)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_no_cache_revalidates_every_time() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut cache = RequestCache::new();
+        mocked_send(
+            &request,
+            b"HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nETag: \"v1\"\r\nContent-Length: 8\r\n\r\nOriginal",
+            &mut cache,
+        )?;
+
+        for _ in 0..2 {
+            let (response, written) =
+                mocked_send(&request, b"HTTP/1.1 304 Not Modified\r\n\r\n", &mut cache)?;
+            assert!(written.contains("If-None-Match: \"v1\"\r\n"));
+            assert_eq!(response.text(), "Original");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_vary_requests_each_variant() -> color_eyre::Result<()> {
+        let url = http_url("http://example.org/");
+        let english = Request::get(&url).header("Accept-Language", "en");
+        let french = Request::get(&url).header("Accept-Language", "fr");
+        let mut cache = RequestCache::new();
+        let raw_response = |body: &str| {
+            format!(
+                "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nVary: Accept-Language\r\n\
+                 Content-Length: {}\r\n\r\n{body}",
+                body.len()
+            )
+        };
+
+        mocked_send(&english, raw_response("Hello").as_bytes(), &mut cache)?;
+        let (response, written) =
+            mocked_send(&french, raw_response("Bonjour").as_bytes(), &mut cache)?;
+        assert!(written.contains("Accept-Language: fr\r\n"));
+        assert_eq!(response.text(), "Bonjour");
+
+        // Both variants are now served from the cache.
+        assert_eq!(mocked_send(&english, b"", &mut cache)?.0.text(), "Hello");
+        assert_eq!(mocked_send(&french, b"", &mut cache)?.0.text(), "Bonjour");
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_uses_stale_response_when_unreachable() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/"));
+        for (cache_control, serves_stale) in
+            [("max-age=0", true), ("max-age=0, must-revalidate", false)]
+        {
+            let mut cache = RequestCache::new();
+            mocked_send(
+                &request,
+                format!(
+                    "HTTP/1.1 200 OK\r\nCache-Control: {cache_control}\r\nETag: \"v1\"\r\n\
+                     Content-Length: 5\r\n\r\nStale"
+                )
+                .as_bytes(),
+                &mut cache,
+            )?;
+
+            let result = mocked_send(&request, b"", &mut cache);
+            if serves_stale {
+                assert_eq!(result?.0.text(), "Stale");
+            } else {
+                let error = result.expect_err("must revalidate");
+                assert!(error.is::<NetworkError>(), "{error}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send_with_pool(
+        request: &Request,
+        connection_pool: &mut ConnectionPool,
+    ) -> color_eyre::Result<Response> {
+        send(
+            request,
+            &mut request_context(
+                connection_pool,
+                &mut RequestCache::new(),
+                &mut CookieJar::new(),
+            ),
+        )
+    }
+
+    fn timeout_error(result: color_eyre::Result<Response>) -> NetworkError {
+        let error = result
+            .expect_err("expected a timeout")
+            .downcast::<NetworkError>()
+            .expect("expected a network error");
+        assert_eq!(error.kind, NetworkErrorKind::Timeout, "{error}");
+        error
+    }
+
+    #[test]
+    fn send_times_out_without_response() {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut connection_pool =
+            ConnectionPool::new().with_read_timeout(Duration::from_millis(20));
+        connection_pool.set_connection(
+            &request.url,
+            Box::new(BlockingStream::new(b"", Duration::ZERO)),
+        );
+
+        let error = timeout_error(send_with_pool(&request, &mut connection_pool));
+
+        assert_eq!(error.message, "No response after 20ms");
+        assert!(connection_pool.connections().is_empty());
+    }
+
+    #[test]
+    fn send_times_out_partway_through_response() {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut connection_pool =
+            ConnectionPool::new().with_read_timeout(Duration::from_millis(20));
+        connection_pool.set_connection(
+            &request.url,
+            Box::new(BlockingStream::new(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\nHello",
+                Duration::ZERO,
+            )),
+        );
+
+        timeout_error(send_with_pool(&request, &mut connection_pool));
+
+        // The rest of the response might still arrive, so the connection can't be reused.
+        assert!(connection_pool.connections().is_empty());
+    }
+
+    #[test]
+    fn send_total_timeout() {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut connection_pool = ConnectionPool::new()
+            .with_read_timeout(Duration::from_secs(1))
+            .with_total_timeout(Duration::from_millis(50));
+        // Each byte arrives well within the read timeout, but the whole response would take
+        // over a second.
+        let mut raw_response = b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n".to_vec();
+        raw_response.extend_from_slice(&[b'a'; 100]);
+        connection_pool.set_connection(
+            &request.url,
+            Box::new(BlockingStream::new(
+                &raw_response,
+                Duration::from_millis(10),
+            )),
+        );
+
+        let start = Instant::now();
+        let error = timeout_error(send_with_pool(&request, &mut connection_pool));
+
+        assert_eq!(error.message, "Request took too long");
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn send_slow_response_within_timeouts() -> color_eyre::Result<()> {
+        let request = Request::get(&http_url("http://example.org/"));
+        let mut connection_pool =
+            ConnectionPool::new().with_read_timeout(Duration::from_millis(50));
+        connection_pool.set_connection(
+            &request.url,
+            Box::new(BlockingStream::new(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nOK",
+                Duration::from_millis(1),
+            )),
+        );
+
+        assert_eq!(send_with_pool(&request, &mut connection_pool)?.text(), "OK");
 
         Ok(())
     }
@@ -0,0 +1,158 @@
+use std::{fmt::Write, time::Duration};
+
+use crate::{
+    config::{
+        profile_dir, CACHE_DIR, CACHE_MAX_SIZE, DEFAULT_HEIGHT, DEFAULT_URL, DEFAULT_WIDTH,
+        SCROLL_BAR_WIDTH,
+    },
+    html::escape,
+    scheme::RequestContext,
+    Url,
+};
+
+use super::{NetworkError, NetworkErrorKind, Response, HTTP_VERSION, REDIRECT_LIMIT, USER_AGENT};
+
+pub fn version_page() -> String {
+    page(
+        "Version",
+        &format!(
+            "<p><b>{}</b> {}</p><p>User agent: {}<br>HTTP version: {}</p>",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            escape(USER_AGENT),
+            HTTP_VERSION,
+        ),
+    )
+}
+
+pub fn cache_page(context: &RequestContext) -> String {
+    let entries = context.cache.entries();
+    let mut body = format!("<p>{} entries</p>", entries.len());
+    if let Some(dir) = context.cache.disk_dir() {
+        let _ = write!(
+            body,
+            "<p>Saved in {}. Press Ctrl+Shift+Delete to clear.</p>",
+            escape(&dir.display().to_string())
+        );
+    }
+    for (url, entry) in entries {
+        let _ = write!(
+            body,
+            "<p><b>{}</b><br>{} bytes, age {}, max age {}, {}</p>",
+            escape(url),
+            entry.size(),
+            format_duration(entry.age()),
+            format_duration(entry.max_age()),
+            if entry.is_stale() { "stale" } else { "fresh" },
+        );
+    }
+    page("Cache", &body)
+}
+
+pub fn connections_page(context: &RequestContext) -> String {
+    let connections = context.connection_pool.connections();
+    let mut body = format!("<p>{} open connections</p><p>", connections.len());
+    for connection in connections {
+        let _ = write!(body, "{}<br>", escape(&connection.to_string()));
+    }
+    body.push_str("</p>");
+    page("Connections", &body)
+}
+
+pub fn history_page(context: &RequestContext) -> String {
+    let mut body = format!("<p>{} pages visited</p><p>", context.history.len());
+    for url in context.history.iter().rev() {
+        let _ = write!(body, "{}<br>", escape(&url.without_userinfo().to_string()));
+    }
+    body.push_str("</p>");
+    page("History", &body)
+}
+
+pub fn config_page() -> String {
+    let settings = [
+        ("Default URL", DEFAULT_URL.to_string()),
+        ("Default width", DEFAULT_WIDTH.to_string()),
+        ("Default height", DEFAULT_HEIGHT.to_string()),
+        ("Scroll bar width", SCROLL_BAR_WIDTH.to_string()),
+        ("Redirect limit", REDIRECT_LIMIT.to_string()),
+        ("Profile directory", profile_dir().display().to_string()),
+        (
+            "Cache directory",
+            profile_dir().join(CACHE_DIR).display().to_string(),
+        ),
+        ("Cache size limit", format!("{CACHE_MAX_SIZE} bytes")),
+    ];
+    let mut body = String::from("<p>");
+    for (name, value) in settings {
+        let _ = write!(body, "<b>{}:</b> {}<br>", name, escape(&value));
+    }
+    body.push_str("</p>");
+    page("Config", &body)
+}
+
+/// Shown instead of the body of a 4xx or 5xx response.
+pub fn error_page(response: &Response) -> String {
+    let description = if response.status < 500 {
+        "The page could not be loaded."
+    } else {
+        "The server had a problem loading the page."
+    };
+    format!(
+        "<html><head><title>{0} {1}</title></head><body><p><big><big><b>{0} {1}</b></big></big></p><p>{2}</p><p>{3}</p></body></html>",
+        response.status,
+        escape(&response.reason),
+        description,
+        escape(&response.url.without_userinfo().to_string()),
+    )
+}
+
+/// Shown when a page couldn't be loaded because the server couldn't be reached.
+pub fn network_error_page(url: &Url, error: &NetworkError) -> String {
+    let description = match error.kind {
+        NetworkErrorKind::Dns => "The server's address could not be found.",
+        NetworkErrorKind::ConnectionRefused => "The server refused the connection.",
+        NetworkErrorKind::Tls => "A secure connection to the server could not be established.",
+        NetworkErrorKind::Timeout => "The server took too long to respond.",
+        NetworkErrorKind::ConnectionClosed => "The server closed the connection unexpectedly.",
+        NetworkErrorKind::InvalidResponse => {
+            "The server sent a response that could not be understood."
+        }
+        NetworkErrorKind::InvalidRedirect => {
+            "The server redirected to an address that could not be followed."
+        }
+        NetworkErrorKind::Other => "The server could not be reached.",
+    };
+    format!(
+        "<html><head><title>{0}</title></head><body><p><big><big><b>{0}</b></big></big></p><p>{1}</p><p>{2}</p><p><small>{3}</small></p></body></html>",
+        error.kind.title(),
+        description,
+        escape(&url.without_userinfo().to_string()),
+        escape(&error.to_string()),
+    )
+}
+
+/// Shown when a page couldn't be loaded for any reason, with the details of a [`NetworkError`]
+/// if that's what went wrong.
+pub fn load_error_page(url: &Url, error: &color_eyre::Report) -> String {
+    if let Some(network_error) = error.downcast_ref::<NetworkError>() {
+        return network_error_page(url, network_error);
+    }
+    format!(
+        "<html><head><title>Page not loaded</title></head><body><p><big><big><b>Page not loaded</b></big></big></p><p>The page could not be loaded.</p><p>{0}</p><p><small>{1}</small></p></body></html>",
+        escape(&url.without_userinfo().to_string()),
+        escape(&error.to_string()),
+    )
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<html><head><title>about:{0}</title></head><body><p><big><big><b>{1}</b></big></big></p>{2}</body></html>",
+        title.to_lowercase(),
+        title,
+        body
+    )
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{}s", duration.as_secs())
+}
@@ -1,20 +1,50 @@
-use rustls::{pki_types::ServerName, ClientConfig};
+use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, StreamOwned};
 use rustls_platform_verifier::ConfigVerifierExt;
 use std::{
     collections::HashMap,
-    io::{BufReader, Read, Write},
-    net::TcpStream,
-    sync::Arc,
+    fmt::Display,
+    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
 };
 
-use crate::url::HttpUrl;
+use color_eyre::eyre::eyre;
 
+use crate::{
+    http::error::{NetworkError, NetworkErrorKind},
+    url::HttpUrl,
+};
+
+/// How long an unused connection is kept open before it's closed.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// The most connections kept open at once, across all hosts.
+const DEFAULT_MAX_CONNECTIONS: usize = 16;
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for the server to send anything before giving up.
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a whole request can take, so a server can't keep us waiting forever by sending a
+/// byte at a time.
+const DEFAULT_TOTAL_TIMEOUT: Duration = Duration::from_secs(120);
+/// The shortest connect and read timeouts, since sockets don't accept a timeout of zero.
+const MIN_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// Keeps HTTP connections open between requests so they can be reused, closing connections
+/// that have gone stale or been left unused for too long.
 pub struct ConnectionPool {
-    connections: HashMap<ConnectionKey, Stream>,
+    connections: HashMap<ConnectionKey, Connection>,
+    idle_timeout: Duration,
+    max_connections: usize,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    total_timeout: Duration,
+    #[cfg(test)]
+    fake_connections: std::collections::VecDeque<Box<dyn ReadWrite>>,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
-struct ConnectionKey {
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConnectionKey {
     pub host: String,
     pub port: u16,
     pub tls: bool,
@@ -30,43 +60,287 @@ impl From<&HttpUrl> for ConnectionKey {
     }
 }
 
-struct Stream(BufReader<Box<dyn ReadWrite>>);
+impl Display for ConnectionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let scheme = if self.tls { "https" } else { "http" };
+        write!(f, "{}://{}:{}", scheme, self.host, self.port)
+    }
+}
+
+struct Connection {
+    stream: BufReader<TimeoutStream>,
+    last_used: Instant,
+    /// Whether a request has already been sent on this connection.
+    used: bool,
+}
+
+impl Connection {
+    fn new(stream: Box<dyn ReadWrite>, read_timeout: Duration) -> Self {
+        Self {
+            stream: BufReader::new(TimeoutStream {
+                inner: stream,
+                read_timeout,
+                deadline: None,
+            }),
+            last_used: Instant::now(),
+            used: false,
+        }
+    }
+
+    /// Whether the server has closed the connection since it was last used.
+    fn is_stale(&self) -> bool {
+        self.stream.get_ref().inner.is_closed()
+    }
+}
+
+/// A connection that gives up on reads once the read timeout passes without any data, or
+/// once the deadline for the current request has passed.
+pub struct TimeoutStream {
+    inner: Box<dyn ReadWrite>,
+    read_timeout: Duration,
+    deadline: Option<Instant>,
+}
+
+impl Read for TimeoutStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let past_deadline =
+            |deadline: Option<Instant>| deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        let too_long = || std::io::Error::new(ErrorKind::TimedOut, "Request took too long");
+
+        let timeout = match self.deadline {
+            Some(deadline) => {
+                // Sockets refuse a zero read timeout, so there's no asking for one.
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(too_long());
+                }
+                self.read_timeout.min(remaining)
+            }
+            None => self.read_timeout,
+        };
+        self.inner.set_read_timeout(Some(timeout))?;
+        self.inner.read(buf).map_err(|error| match error.kind() {
+            // Sockets report a read timeout as `WouldBlock` on Unix and `TimedOut` on Windows.
+            ErrorKind::WouldBlock | ErrorKind::TimedOut if past_deadline(self.deadline) => {
+                too_long()
+            }
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => std::io::Error::new(
+                ErrorKind::TimedOut,
+                format!("No response after {}ms", timeout.as_millis()),
+            ),
+            _ => error,
+        })
+    }
+}
+
+impl Write for TimeoutStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
 
 impl ConnectionPool {
     pub fn new() -> Self {
         Self {
             connections: HashMap::new(),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            total_timeout: DEFAULT_TOTAL_TIMEOUT,
+            #[cfg(test)]
+            fake_connections: Default::default(),
         }
     }
 
-    pub fn get_connection(
+    /// Sets how long an unused connection is kept open.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Sets the most connections to keep open. When a new connection would go over the
+    /// limit, the least recently used connection is closed.
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections.max(1);
+        self
+    }
+
+    /// Sets how long to wait for the host name to be looked up, and separately for a new
+    /// connection to be established. Timeouts shorter than a millisecond are rounded up.
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout.max(MIN_TIMEOUT);
+        self
+    }
+
+    /// Sets how long to wait for the server to send something before giving up. Timeouts
+    /// shorter than a millisecond are rounded up.
+    pub fn with_read_timeout(mut self, read_timeout: Duration) -> Self {
+        let read_timeout = read_timeout.max(MIN_TIMEOUT);
+        self.read_timeout = read_timeout;
+        for connection in self.connections.values_mut() {
+            connection.stream.get_mut().read_timeout = read_timeout;
+        }
+        self
+    }
+
+    /// Sets how long a whole request can take, from sending it to reading the end of the
+    /// response.
+    pub fn with_total_timeout(mut self, total_timeout: Duration) -> Self {
+        self.total_timeout = total_timeout;
+        self
+    }
+
+    /// Writes a request to a connection to the URL's host, reusing an open connection if
+    /// there is one, and waits for the response to start arriving.
+    ///
+    /// Servers can close a kept-alive connection at any time, so if `can_retry` is set and a
+    /// reused connection turns out to be closed, the request is sent again on a new
+    /// connection. This should only be done for idempotent requests.
+    ///
+    /// Reading from the returned stream fails with a [`ErrorKind::TimedOut`] error once the
+    /// read timeout or the total timeout for the request passes.
+    pub fn send_request(
         &mut self,
         http_url: &HttpUrl,
-    ) -> color_eyre::Result<&mut BufReader<Box<dyn ReadWrite>>> {
-        let num_connections = self.connections.len();
-        Ok(&mut self
+        request: &[u8],
+        can_retry: bool,
+    ) -> color_eyre::Result<&mut BufReader<TimeoutStream>> {
+        let deadline = Instant::now() + self.total_timeout;
+        let connection = self.get_connection(http_url)?;
+        let reused = connection.used;
+        connection.used = true;
+        connection.stream.get_mut().deadline = Some(deadline);
+        let mut result = write_request(&mut connection.stream, request);
+
+        let was_closed = match &result {
+            Ok(received_response) => !received_response,
+            Err(error) => matches!(
+                error.kind(),
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::UnexpectedEof
+            ),
+        };
+        if reused && can_retry && was_closed {
+            tracing::info!("Reused connection was closed, retrying on a new connection");
+            self.remove_connection(http_url);
+            let connection = self.get_connection(http_url)?;
+            connection.used = true;
+            connection.stream.get_mut().deadline = Some(deadline);
+            result = write_request(&mut connection.stream, request);
+        }
+        let server = ConnectionKey::from(http_url).to_string();
+        match result {
+            Ok(true) => {}
+            Ok(false) => {
+                self.remove_connection(http_url);
+                return Err(NetworkError::new(
+                    NetworkErrorKind::ConnectionClosed,
+                    &server,
+                    "Connection closed before sending a response",
+                )
+                .into());
+            }
+            Err(error) => {
+                self.remove_connection(http_url);
+                return Err(NetworkError::from_io(&server, error).into());
+            }
+        }
+
+        let key = ConnectionKey::from(http_url);
+        self.connections
+            .get_mut(&key)
+            .map(|connection| &mut connection.stream)
+            .ok_or_else(|| eyre!("No connection to {key}"))
+    }
+
+    fn get_connection(&mut self, http_url: &HttpUrl) -> color_eyre::Result<&mut Connection> {
+        let key = ConnectionKey::from(http_url);
+        if let Some(connection) = self.connections.get(&key) {
+            if connection.last_used.elapsed() >= self.idle_timeout {
+                tracing::info!("Closing idle connection to {}", key);
+                self.connections.remove(&key);
+            } else if connection.used && connection.is_stale() {
+                tracing::info!("Connection to {} was closed by the server", key);
+                self.connections.remove(&key);
+            }
+        }
+
+        if !self.connections.contains_key(&key) {
+            if self.connections.len() >= self.max_connections {
+                self.evict_least_recently_used();
+            }
+            tracing::info!(
+                "Connecting to {} (total connections: {})",
+                key,
+                self.connections.len() + 1
+            );
+            let stream = self.connect(http_url)?;
+            self.connections
+                .insert(key.clone(), Connection::new(stream, self.read_timeout));
+        }
+
+        let connection = self
             .connections
-            .entry(http_url.into())
-            .or_insert_with(|| {
-                tracing::info!(
-                    "Connecting to http{}://{}:{} (total connections: {})",
-                    if http_url.tls { "s" } else { "" },
-                    http_url.host,
-                    http_url.port,
-                    num_connections + 1
-                );
-                Stream(BufReader::new(match http_url.tls {
-                    false => connect_http(http_url).unwrap(),
-                    true => connect_https(http_url).unwrap(),
-                }))
-            })
-            .0)
+            .get_mut(&key)
+            .ok_or_else(|| eyre!("No connection to {key}"))?;
+        connection.last_used = Instant::now();
+        Ok(connection)
+    }
+
+    fn connect(&mut self, http_url: &HttpUrl) -> Result<Box<dyn ReadWrite>, NetworkError> {
+        #[cfg(test)]
+        if let Some(stream) = self.fake_connections.pop_front() {
+            return Ok(stream);
+        }
+        match http_url.tls {
+            false => connect_http(http_url, self.connect_timeout, self.read_timeout),
+            true => connect_https(http_url, self.connect_timeout, self.read_timeout),
+        }
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        let oldest = self
+            .connections
+            .iter()
+            .min_by_key(|(_, connection)| connection.last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = oldest {
+            tracing::info!("Too many open connections, closing connection to {}", key);
+            self.connections.remove(&key);
+        }
+    }
+
+    /// Closes the connection to the URL's host, e.g. after a `Connection: close` response.
+    pub fn remove_connection(&mut self, http_url: &HttpUrl) {
+        if self.connections.remove(&http_url.into()).is_some() {
+            tracing::info!("Closed connection to {}", ConnectionKey::from(http_url));
+        }
+    }
+
+    /// The hosts we currently have an open connection to, sorted by host and port.
+    pub fn connections(&self) -> Vec<&ConnectionKey> {
+        let mut keys: Vec<_> = self.connections.keys().collect();
+        keys.sort_by(|a, b| (&a.host, a.port, a.tls).cmp(&(&b.host, b.port, b.tls)));
+        keys
     }
 
     #[cfg(test)]
     pub fn set_connection(&mut self, http_url: &HttpUrl, conn: Box<dyn ReadWrite>) {
         self.connections
-            .insert(http_url.into(), Stream(BufReader::new(conn)));
+            .insert(http_url.into(), Connection::new(conn, self.read_timeout));
+    }
+
+    /// Makes the next new connection use this stream instead of connecting to a server.
+    #[cfg(test)]
+    pub fn queue_connection(&mut self, conn: Box<dyn ReadWrite>) {
+        self.fake_connections.push_back(conn);
     }
 }
 
@@ -76,38 +350,434 @@ impl Default for ConnectionPool {
     }
 }
 
-pub trait ReadWrite: Read + Write {}
-impl<T: Read + Write> ReadWrite for T {}
+/// Writes a request and waits for the first byte of the response, returning `false` if the
+/// connection was closed instead.
+fn write_request(stream: &mut BufReader<TimeoutStream>, request: &[u8]) -> std::io::Result<bool> {
+    stream.get_mut().write_all(request)?;
+    stream.get_mut().flush()?;
+    Ok(!stream.fill_buf()?.is_empty())
+}
 
-fn connect_http(http_url: &HttpUrl) -> color_eyre::Result<Box<dyn ReadWrite>> {
-    let sock = TcpStream::connect((http_url.host.as_str(), http_url.port))?;
-    Ok(Box::new(sock))
+pub trait ReadWrite: Read + Write {
+    /// Whether the other end has closed the connection, checked without blocking.
+    fn is_closed(&self) -> bool;
+
+    /// Makes reads fail with `WouldBlock` or `TimedOut` if no data arrives in time.
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()>;
+}
+
+impl ReadWrite for TcpStream {
+    fn is_closed(&self) -> bool {
+        is_socket_closed(self)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
 }
 
-fn connect_https(http_url: &HttpUrl) -> color_eyre::Result<Box<dyn ReadWrite>> {
+impl ReadWrite for StreamOwned<ClientConnection, TcpStream> {
+    fn is_closed(&self) -> bool {
+        // Servers send TLS 1.3 session tickets after the handshake, so data waiting on the
+        // socket is normal. A `close_notify` alert is data too, so a close is only noticed here
+        // once the socket reaches EOF, or otherwise when the request fails and is retried.
+        is_socket_closed(&self.sock)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.sock.set_read_timeout(timeout)
+    }
+}
+
+/// Whether the socket has reached EOF or failed. Data waiting to be read doesn't count.
+fn is_socket_closed(sock: &TcpStream) -> bool {
+    if sock.set_nonblocking(true).is_err() {
+        return true;
+    }
+    let mut buf = [0; 1];
+    let closed = match sock.peek(&mut buf) {
+        Ok(length) => length == 0,
+        Err(error) => error.kind() != ErrorKind::WouldBlock,
+    };
+    sock.set_nonblocking(false).is_err() || closed
+}
+
+/// Connects to the first of the host's addresses that accepts a connection within the
+/// timeout.
+fn connect_tcp(
+    http_url: &HttpUrl,
+    connect_timeout: Duration,
+    write_timeout: Duration,
+) -> Result<TcpStream, NetworkError> {
+    let server = ConnectionKey::from(http_url).to_string();
+    let addresses =
+        resolve(http_url.connect_host(), http_url.port, connect_timeout).map_err(|error| {
+            match error.kind() {
+                ErrorKind::TimedOut => NetworkError::from_io(&server, error),
+                _ => NetworkError::new(NetworkErrorKind::Dns, &server, error),
+            }
+        })?;
+
+    let mut last_error = None;
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, connect_timeout) {
+            Ok(sock) => {
+                sock.set_write_timeout(Some(write_timeout))
+                    .map_err(|error| NetworkError::from_io(&server, error))?;
+                return Ok(sock);
+            }
+            Err(error) => last_error = Some(error),
+        }
+    }
+    Err(match last_error {
+        Some(error) => NetworkError::from_io(&server, error),
+        None => NetworkError::new(NetworkErrorKind::Dns, &server, "No addresses found"),
+    })
+}
+
+/// Looks up the addresses of a host, giving up once the timeout passes. The system resolver
+/// can't be interrupted, so a lookup that takes too long carries on in the background and its
+/// result is thrown away.
+fn resolve(host: &str, port: u16, timeout: Duration) -> std::io::Result<Vec<SocketAddr>> {
+    // IP addresses don't need looking up.
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let host = host.to_string();
+    thread::Builder::new()
+        .name("dns".to_string())
+        .spawn(move || {
+            let addresses = (host.as_str(), port)
+                .to_socket_addrs()
+                .map(|addresses| addresses.collect());
+            // Nobody is listening any more if the lookup timed out.
+            let _ = sender.send(addresses);
+        })?;
+    receiver.recv_timeout(timeout).unwrap_or_else(|_| {
+        Err(std::io::Error::new(
+            ErrorKind::TimedOut,
+            format!("Host name lookup took over {}ms", timeout.as_millis()),
+        ))
+    })
+}
+
+fn connect_http(
+    http_url: &HttpUrl,
+    connect_timeout: Duration,
+    write_timeout: Duration,
+) -> Result<Box<dyn ReadWrite>, NetworkError> {
+    Ok(Box::new(connect_tcp(
+        http_url,
+        connect_timeout,
+        write_timeout,
+    )?))
+}
+
+fn connect_https(
+    http_url: &HttpUrl,
+    connect_timeout: Duration,
+    write_timeout: Duration,
+) -> Result<Box<dyn ReadWrite>, NetworkError> {
+    let server = ConnectionKey::from(http_url).to_string();
+    let tls_error = |error: &dyn Display| NetworkError::new(NetworkErrorKind::Tls, &server, error);
     let config = ClientConfig::with_platform_verifier();
-    let conn = rustls::ClientConnection::new(
-        Arc::new(config),
-        ServerName::try_from(http_url.host.to_string())?,
-    )?;
-    let sock = TcpStream::connect((http_url.host.as_str(), http_url.port))?;
-    Ok(Box::new(rustls::StreamOwned::new(conn, sock)))
+    let server_name = ServerName::try_from(http_url.connect_host().to_string())
+        .map_err(|error| tls_error(&error))?;
+    let conn =
+        ClientConnection::new(Arc::new(config), server_name).map_err(|error| tls_error(&error))?;
+    let sock = connect_tcp(http_url, connect_timeout, write_timeout)?;
+    Ok(Box::new(StreamOwned::new(conn, sock)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::{fake::FakeStream, *};
+    use crate::http::testing::http_url;
+
+    fn read_to_string(stream: &mut impl Read) -> String {
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn network_error<T>(result: color_eyre::Result<T>) -> NetworkError {
+        match result {
+            Ok(_) => panic!("expected a network error"),
+            Err(error) => error.downcast().expect("expected a network error"),
+        }
+    }
+
+    #[test]
+    fn send_request_reuses_connection() -> color_eyre::Result<()> {
+        let url = http_url("http://example.org/");
+        let stream = FakeStream::new(b"first");
+        let written = stream.written();
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(&url, Box::new(stream));
+
+        connection_pool.send_request(&url, b"one", true)?;
+        assert_eq!(written.borrow().as_slice(), b"one");
+        assert_eq!(connection_pool.connections().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_request_retries_closed_connection() -> color_eyre::Result<()> {
+        let url = http_url("http://example.org/");
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(&url, Box::new(FakeStream::new(b"first")));
+        connection_pool.queue_connection(Box::new(FakeStream::new(b"second")));
+
+        let stream = connection_pool.send_request(&url, b"GET", true)?;
+        assert_eq!(read_to_string(stream), "first");
+        // The server has nothing more to say, as if it closed the connection.
+        let stream = connection_pool.send_request(&url, b"GET", true)?;
+        assert_eq!(read_to_string(stream), "second");
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_request_does_not_retry_non_idempotent() -> color_eyre::Result<()> {
+        let url = http_url("http://example.org/");
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(&url, Box::new(FakeStream::new(b"first")));
+        connection_pool.queue_connection(Box::new(FakeStream::new(b"second")));
+
+        let stream = connection_pool.send_request(&url, b"POST", false)?;
+        read_to_string(stream);
+        assert!(connection_pool.send_request(&url, b"POST", false).is_err());
+        assert!(connection_pool.connections().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn send_request_fails_on_new_closed_connection() {
+        let url = http_url("http://example.org/");
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.set_connection(&url, Box::new(FakeStream::new(b"")));
+        connection_pool.queue_connection(Box::new(FakeStream::new(b"unused")));
+
+        // A brand new connection closing isn't a stale keep-alive connection, so there's no
+        // retry.
+        let error = network_error(connection_pool.send_request(&url, b"GET", true));
+        assert_eq!(error.kind, NetworkErrorKind::ConnectionClosed);
+        assert_eq!(error.server, "http://example.org:80");
+    }
+
+    #[test]
+    fn idle_connections_are_closed() -> color_eyre::Result<()> {
+        let url = http_url("http://example.org/");
+        let old = FakeStream::new(b"old");
+        let old_written = old.written();
+        let mut connection_pool = ConnectionPool::new().with_idle_timeout(Duration::ZERO);
+        connection_pool.set_connection(&url, Box::new(old));
+        connection_pool.queue_connection(Box::new(FakeStream::new(b"new")));
+
+        let stream = connection_pool.send_request(&url, b"GET", true)?;
+        assert_eq!(read_to_string(stream), "new");
+        assert!(old_written.borrow().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn max_connections_closes_least_recently_used() -> color_eyre::Result<()> {
+        let (a, b, c) = (
+            http_url("http://a.example/"),
+            http_url("http://b.example/"),
+            http_url("http://c.example/"),
+        );
+        let mut connection_pool = ConnectionPool::new().with_max_connections(2);
+        connection_pool.set_connection(&a, Box::new(FakeStream::new(b"a")));
+        connection_pool.set_connection(&b, Box::new(FakeStream::new(b"b")));
+        connection_pool.send_request(&a, b"GET", true)?;
+        connection_pool.queue_connection(Box::new(FakeStream::new(b"c")));
+        connection_pool.send_request(&c, b"GET", true)?;
+
+        let hosts: Vec<_> = connection_pool
+            .connections()
+            .iter()
+            .map(|key| key.host.as_str())
+            .collect();
+        assert_eq!(hosts, vec!["a.example", "c.example"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn detects_closed_socket() -> color_eyre::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let url = http_url(&format!("http://127.0.0.1:{port}/"));
+        let mut connection_pool = ConnectionPool::new();
+
+        let server = std::thread::spawn(move || -> std::io::Result<()> {
+            for response in [b"one", b"two"] {
+                let (mut socket, _) = listener.accept()?;
+                let mut request = [0; 3];
+                socket.read_exact(&mut request)?;
+                socket.write_all(response)?;
+            }
+            Ok(())
+        });
+
+        let stream = connection_pool.send_request(&url, b"GET", false)?;
+        let mut response = [0; 3];
+        stream.read_exact(&mut response)?;
+        assert_eq!(&response, b"one");
+
+        // Wait for the server to close the first connection. Retrying is turned off, so the
+        // second request only succeeds if the pool notices the close before sending it.
+        assert_eq!(stream.read(&mut response)?, 0);
+        let stream = connection_pool.send_request(&url, b"GET", false)?;
+        stream.read_exact(&mut response)?;
+        assert_eq!(&response, b"two");
+        server.join().unwrap()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_at_deadline_times_out() {
+        let mut stream = TimeoutStream {
+            inner: Box::new(fake::BlockingStream::new(b"late", Duration::ZERO)),
+            read_timeout: Duration::from_secs(1),
+            deadline: Some(Instant::now()),
+        };
+        let error = stream.read(&mut [0; 4]).expect_err("past the deadline");
+        assert_eq!(error.kind(), ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn zero_timeouts_are_rounded_up() {
+        let connection_pool = ConnectionPool::new()
+            .with_connect_timeout(Duration::ZERO)
+            .with_read_timeout(Duration::ZERO);
+        assert_eq!(connection_pool.connect_timeout, MIN_TIMEOUT);
+        assert_eq!(connection_pool.read_timeout, MIN_TIMEOUT);
+    }
+
+    #[test]
+    fn resolving_ip_addresses_is_immediate() -> color_eyre::Result<()> {
+        assert_eq!(
+            resolve("::1", 80, Duration::from_nanos(1))?,
+            vec![SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], 80))]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unread_data_is_not_a_closed_socket() -> color_eyre::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let client = TcpStream::connect(listener.local_addr()?)?;
+        let (mut server, _) = listener.accept()?;
+
+        // Like a TLS session ticket arriving after the handshake.
+        server.write_all(b"ticket")?;
+        client.peek(&mut [0; 1])?;
+        assert!(!is_socket_closed(&client));
+
+        drop(server);
+        let mut data = Vec::new();
+        (&client).read_to_end(&mut data)?;
+        assert_eq!(data, b"ticket");
+        assert!(is_socket_closed(&client));
+
+        Ok(())
+    }
+
+    #[test]
+    fn connection_refused_is_an_error() -> color_eyre::Result<()> {
+        // Find a port nothing is listening on.
+        let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+        let url = http_url(&format!("http://127.0.0.1:{port}/"));
+
+        let error = network_error(ConnectionPool::new().send_request(&url, b"GET", true));
+        assert_eq!(error.kind, NetworkErrorKind::ConnectionRefused);
+        assert_eq!(error.server, format!("http://127.0.0.1:{port}"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_host_is_an_error() {
+        let url = http_url("http://bowsernet.invalid/");
+
+        let error = network_error(ConnectionPool::new().send_request(&url, b"GET", true));
+        assert_eq!(error.kind, NetworkErrorKind::Dns);
+    }
+
+    #[test]
+    fn tls_failure_is_an_error() -> color_eyre::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let url = http_url(&format!("https://localhost:{port}/"));
+        let server = std::thread::spawn(move || -> std::io::Result<()> {
+            // Answer the TLS handshake with plain HTTP.
+            let (mut socket, _) = listener.accept()?;
+            socket.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n")?;
+            Ok(())
+        });
+
+        let error = network_error(ConnectionPool::new().send_request(&url, b"GET", true));
+        assert_eq!(error.kind, NetworkErrorKind::Tls);
+        server.join().unwrap()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn connects_to_ipv6_loopback() -> color_eyre::Result<()> {
+        let Ok(listener) = TcpListener::bind("[::1]:0") else {
+            // IPv6 isn't available in every environment.
+            return Ok(());
+        };
+        let port = listener.local_addr()?.port();
+        let http_url = http_url(&format!("http://[::1]:{port}/"));
+
+        let mut connection_pool = ConnectionPool::new();
+        connection_pool.get_connection(&http_url)?;
+
+        assert!(listener.accept().is_ok());
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 pub mod fake {
-    use std::io::{Cursor, Read, Write};
+    use std::{
+        cell::RefCell,
+        io::{Cursor, ErrorKind, Read, Write},
+        rc::Rc,
+        time::Duration,
+    };
 
     pub struct FakeStream {
         response: Cursor<Vec<u8>>,
+        written: Rc<RefCell<Vec<u8>>>,
     }
 
     impl FakeStream {
         pub fn new(response: &[u8]) -> Self {
             Self {
                 response: Cursor::new(response.to_vec()),
+                written: Rc::new(RefCell::new(Vec::new())),
             }
         }
+
+        /// A handle to everything written to the stream, which stays usable after the stream
+        /// has been moved into a connection pool.
+        pub fn written(&self) -> Rc<RefCell<Vec<u8>>> {
+            Rc::clone(&self.written)
+        }
     }
 
     impl Read for FakeStream {
@@ -116,8 +786,81 @@ pub mod fake {
         }
     }
 
+    impl super::ReadWrite for FakeStream {
+        // Like a real socket whose close hasn't been noticed yet, so that tests can exercise
+        // retrying on a new connection.
+        fn is_closed(&self) -> bool {
+            false
+        }
+
+        fn set_read_timeout(&mut self, _timeout: Option<Duration>) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Like a slow or hung server: sends its response a byte at a time with `delay` before
+    /// each byte, then never sends anything else. Like a real socket, reads give up with
+    /// `WouldBlock` once the read timeout passes.
+    pub struct BlockingStream {
+        response: Cursor<Vec<u8>>,
+        delay: Duration,
+        read_timeout: Option<Duration>,
+    }
+
+    impl BlockingStream {
+        pub fn new(response: &[u8], delay: Duration) -> Self {
+            Self {
+                response: Cursor::new(response.to_vec()),
+                delay,
+                read_timeout: None,
+            }
+        }
+    }
+
+    impl Read for BlockingStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let timeout = self
+                .read_timeout
+                .expect("BlockingStream would block forever without a read timeout");
+            let is_done = self.response.position() >= self.response.get_ref().len() as u64;
+            if is_done || self.delay >= timeout {
+                std::thread::sleep(timeout);
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            std::thread::sleep(self.delay);
+            let length = buf.len().min(1);
+            self.response.read(&mut buf[..length])
+        }
+    }
+
+    impl Write for BlockingStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl super::ReadWrite for BlockingStream {
+        fn is_closed(&self) -> bool {
+            false
+        }
+
+        fn set_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+            // Real sockets reject a zero timeout too.
+            if timeout == Some(Duration::ZERO) {
+                return Err(ErrorKind::InvalidInput.into());
+            }
+            self.read_timeout = timeout;
+            Ok(())
+        }
+    }
+
     impl Write for FakeStream {
         fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.borrow_mut().extend_from_slice(buf);
             Ok(buf.len())
         }
 
@@ -0,0 +1,184 @@
+use std::io::Read;
+
+use color_eyre::eyre::eyre;
+use flate2::bufread::{DeflateDecoder, GzDecoder, ZlibDecoder};
+
+/// The content codings we can decode, for the `Accept-Encoding` request header.
+pub const ACCEPT_ENCODING: &str = "gzip, deflate, br, zstd";
+/// The biggest a body can get once it's decoded, so a small compressed body can't expand to
+/// fill up memory.
+pub const MAX_DECODED_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentCoding {
+    Identity,
+    Gzip,
+    Deflate,
+    Brotli,
+    Zstd,
+}
+
+impl ContentCoding {
+    pub fn parse(name: &str) -> color_eyre::Result<Self> {
+        match name.trim_ascii().to_ascii_lowercase().as_str() {
+            "identity" => Ok(Self::Identity),
+            "gzip" | "x-gzip" => Ok(Self::Gzip),
+            "deflate" => Ok(Self::Deflate),
+            "br" => Ok(Self::Brotli),
+            "zstd" => Ok(Self::Zstd),
+            other => Err(eyre!("Unsupported content-encoding: {other}")),
+        }
+    }
+
+    /// Decodes a body, failing if it would decode to more than `max_size` bytes.
+    fn decode(self, body: &[u8], max_size: usize) -> color_eyre::Result<Vec<u8>> {
+        // Reading one byte more than allowed shows whether there was more.
+        let limit = max_size as u64 + 1;
+        let mut decoded = Vec::new();
+        match self {
+            Self::Identity => decoded.extend_from_slice(body),
+            Self::Gzip => {
+                GzDecoder::new(body).take(limit).read_to_end(&mut decoded)?;
+            }
+            Self::Deflate => {
+                // `deflate` is meant to be zlib-wrapped, but some servers send raw deflate data.
+                let zlib = ZlibDecoder::new(body).take(limit).read_to_end(&mut decoded);
+                if zlib.is_err() {
+                    decoded.clear();
+                    DeflateDecoder::new(body)
+                        .take(limit)
+                        .read_to_end(&mut decoded)?;
+                }
+            }
+            Self::Brotli => {
+                brotli_decompressor::Decompressor::new(body, 4096)
+                    .take(limit)
+                    .read_to_end(&mut decoded)?;
+            }
+            Self::Zstd => {
+                ruzstd::decoding::StreamingDecoder::new(body)
+                    .map_err(|error| eyre!("Invalid zstd data: {error}"))?
+                    .take(limit)
+                    .read_to_end(&mut decoded)?;
+            }
+        }
+        if decoded.len() > max_size {
+            return Err(eyre!("Decoded body is larger than {max_size} bytes"));
+        }
+        Ok(decoded)
+    }
+}
+
+/// Decodes a body according to its `Content-Encoding` header, which lists the codings in the
+/// order they were applied, e.g. `gzip, br` was gzipped and then compressed with Brotli.
+/// Bodies that decode to more than [`MAX_DECODED_SIZE`] are an error.
+pub fn decode_content(content_encoding: &str, body: Vec<u8>) -> color_eyre::Result<Vec<u8>> {
+    decode_content_limited(content_encoding, body, MAX_DECODED_SIZE)
+}
+
+fn decode_content_limited(
+    content_encoding: &str,
+    body: Vec<u8>,
+    max_size: usize,
+) -> color_eyre::Result<Vec<u8>> {
+    let codings = content_encoding
+        .split(',')
+        .filter(|coding| !coding.trim_ascii().is_empty())
+        .map(ContentCoding::parse)
+        .collect::<color_eyre::Result<Vec<_>>>()?;
+
+    let mut body = body;
+    for coding in codings.into_iter().rev() {
+        tracing::info!("Decoding {:?} response", coding);
+        body = coding.decode(&body, max_size)?;
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GZIP: &[u8] = b"\x1F\x8B\x08\x00\x00\x00\x00\x00\x00\x03\xF3\x48\xCD\xC9\xC9\xD7\x51\x28\xCF\x2F\xCA\x49\x51\x04\x00\xE6\xC6\xE6\xEB\x0D\x00\x00\x00";
+    const ZLIB: &[u8] =
+        b"\x78\x9C\xF3\x48\xCD\xC9\xC9\xD7\x51\x28\xCF\x2F\xCA\x49\x51\x04\x00\x20\x5E\x04\x8A";
+    const RAW_DEFLATE: &[u8] = b"\xF3\x48\xCD\xC9\xC9\xD7\x51\x28\xCF\x2F\xCA\x49\x51\x04\x00";
+    const BROTLI: &[u8] = b"\x0B\x06\x80\x48\x65\x6C\x6C\x6F\x2C\x20\x77\x6F\x72\x6C\x64\x21\x03";
+    const ZSTD: &[u8] = b"\x28\xB5\x2F\xFD\x04\x68\x69\x00\x00\x48\x65\x6C\x6C\x6F\x2C\x20\x77\x6F\x72\x6C\x64\x21\x76\x94\x6F\x8B";
+
+    #[test]
+    fn decode_each_coding() -> color_eyre::Result<()> {
+        for (content_encoding, body) in [
+            ("gzip", GZIP),
+            ("x-gzip", GZIP),
+            ("deflate", ZLIB),
+            ("deflate", RAW_DEFLATE),
+            ("br", BROTLI),
+            ("zstd", ZSTD),
+            ("identity", b"Hello, world!"),
+            ("", b"Hello, world!"),
+            (" GZip ", GZIP),
+        ] {
+            assert_eq!(
+                decode_content(content_encoding, body.to_vec())?,
+                b"Hello, world!",
+                "{content_encoding}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn decode_stacked_codings() -> color_eyre::Result<()> {
+        let gzip_then_brotli = b"\x0B\x10\x80\x1F\x8B\x08\x00\x00\x00\x00\x00\x00\x03\xF3\x48\xCD\xC9\xC9\xD7\x51\x28\xCF\x2F\xCA\x49\x51\x04\x00\xE6\xC6\xE6\xEB\x0D\x00\x00\x00\x03";
+        assert_eq!(
+            decode_content("gzip, br", gzip_then_brotli.to_vec())?,
+            b"Hello, world!"
+        );
+        assert_eq!(
+            decode_content("identity, gzip,identity", GZIP.to_vec())?,
+            b"Hello, world!"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn decoded_size_is_limited() -> color_eyre::Result<()> {
+        for (content_encoding, body) in [
+            ("gzip", GZIP),
+            ("deflate", ZLIB),
+            ("deflate", RAW_DEFLATE),
+            ("br", BROTLI),
+            ("zstd", ZSTD),
+        ] {
+            assert_eq!(
+                decode_content_limited(content_encoding, body.to_vec(), 13)?,
+                b"Hello, world!",
+                "{content_encoding}"
+            );
+            let error = decode_content_limited(content_encoding, body.to_vec(), 12).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "Decoded body is larger than 12 bytes",
+                "{content_encoding}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_coding_is_an_error() {
+        let error = decode_content("gzip, compress", GZIP.to_vec()).unwrap_err();
+        assert_eq!(error.to_string(), "Unsupported content-encoding: compress");
+    }
+
+    #[test]
+    fn corrupt_data_is_an_error() {
+        for content_encoding in ["gzip", "br", "zstd"] {
+            assert!(
+                decode_content(content_encoding, b"Hello, world!".to_vec()).is_err(),
+                "{content_encoding}"
+            );
+        }
+    }
+}
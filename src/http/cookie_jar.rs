@@ -0,0 +1,772 @@
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    http::{date::parse_http_date, Headers},
+    url::{public_suffix::public_suffix, HttpUrl},
+};
+
+/// The most cookies kept for each domain, and in total.
+const MAX_COOKIES_PER_DOMAIN: usize = 50;
+const MAX_COOKIES: usize = 3000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// The lowercased ASCII host or domain the cookie belongs to.
+    pub domain: String,
+    /// Whether the cookie is only sent to `domain` itself and not its subdomains, because it
+    /// was set without a valid `Domain` attribute.
+    pub host_only: bool,
+    pub path: String,
+    /// When the cookie expires, or `None` for a session cookie that only lasts until the
+    /// browser is closed.
+    pub expires: Option<SystemTime>,
+    pub secure: bool,
+    pub http_only: bool,
+    /// Defaults to `Lax` when the attribute is missing, as in current browsers.
+    pub same_site: SameSite,
+    pub created: SystemTime,
+}
+
+/// Stores cookies from `Set-Cookie` headers and works out which ones to send with each request,
+/// following RFC 6265.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn cookies(&self) -> &[Cookie] {
+        &self.cookies
+    }
+
+    /// Stores the cookies from every `Set-Cookie` header of a response.
+    pub fn store_response_cookies(&mut self, url: &HttpUrl, headers: &Headers) {
+        for header in headers.get_all("set-cookie") {
+            self.set_cookie(url, header);
+        }
+    }
+
+    /// Stores a cookie from a `Set-Cookie` header value received from `url`. Invalid cookies,
+    /// and cookies the server isn't allowed to set, are ignored.
+    pub fn set_cookie(&mut self, url: &HttpUrl, header: &str) {
+        self.set_cookie_at(url, header, SystemTime::now());
+    }
+
+    fn set_cookie_at(&mut self, url: &HttpUrl, header: &str, now: SystemTime) {
+        let Some(mut cookie) = parse_set_cookie(url, header, now) else {
+            tracing::info!("Ignoring invalid cookie: {}", header);
+            return;
+        };
+
+        let existing = self.cookies.iter().position(|existing| {
+            existing.name == cookie.name
+                && existing.domain == cookie.domain
+                && existing.host_only == cookie.host_only
+                && existing.path == cookie.path
+        });
+        if let Some(index) = existing {
+            cookie.created = self.cookies.remove(index).created;
+        }
+        if !is_expired(&cookie, now) {
+            let domain = cookie.domain.clone();
+            self.cookies.push(cookie);
+            self.evict_excess(&domain, now);
+        }
+    }
+
+    /// Keeps the jar within the limits suggested by RFC 6265 section 6.1, so a server can't
+    /// fill it up. Expired cookies go first, then the oldest ones.
+    fn evict_excess(&mut self, domain: &str, now: SystemTime) {
+        self.cookies.retain(|cookie| !is_expired(cookie, now));
+        while self
+            .cookies
+            .iter()
+            .filter(|cookie| cookie.domain == domain)
+            .count()
+            > MAX_COOKIES_PER_DOMAIN
+        {
+            self.remove_oldest(|cookie| cookie.domain == domain);
+        }
+        while self.cookies.len() > MAX_COOKIES {
+            self.remove_oldest(|_| true);
+        }
+    }
+
+    fn remove_oldest(&mut self, filter: impl Fn(&Cookie) -> bool) {
+        let oldest = self
+            .cookies
+            .iter()
+            .enumerate()
+            .filter(|(_, cookie)| filter(cookie))
+            .min_by_key(|(_, cookie)| cookie.created)
+            .map(|(index, _)| index);
+        if let Some(index) = oldest {
+            let cookie = self.cookies.remove(index);
+            tracing::info!("Evicting cookie {} for {}", cookie.name, cookie.domain);
+        }
+    }
+
+    /// The value of the `Cookie` header to send with a request to `url`, if any cookies match.
+    pub fn cookie_header(&self, url: &HttpUrl) -> Option<String> {
+        self.cookie_header_at(url, SystemTime::now())
+    }
+
+    fn cookie_header_at(&self, url: &HttpUrl, now: SystemTime) -> Option<String> {
+        let mut cookies: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|cookie| {
+                let domain_matches = if cookie.host_only {
+                    url.host == cookie.domain
+                } else {
+                    domain_matches(&url.host, &cookie.domain)
+                };
+                domain_matches
+                    && path_matches(&url.path, &cookie.path)
+                    && (url.tls || !cookie.secure)
+                    && !is_expired(cookie, now)
+            })
+            .collect();
+        if cookies.is_empty() {
+            return None;
+        }
+
+        // Cookies with longer paths are listed first, as recommended by RFC 6265.
+        cookies.sort_by(|a, b| {
+            b.path
+                .len()
+                .cmp(&a.path.len())
+                .then(a.created.cmp(&b.created))
+        });
+        let pairs: Vec<String> = cookies
+            .iter()
+            .map(|cookie| {
+                if cookie.name.is_empty() {
+                    cookie.value.clone()
+                } else {
+                    format!("{}={}", cookie.name, cookie.value)
+                }
+            })
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    /// Loads cookies saved by [`CookieJar::save`]. A missing file gives an empty jar.
+    pub fn load(path: &Path) -> color_eyre::Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut jar = Self::new();
+        for line in contents.lines() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match parse_saved_cookie(line) {
+                Some(cookie) => jar.cookies.push(cookie),
+                None => tracing::warn!("Skipping malformed line in cookie file: {}", line),
+            }
+        }
+        Ok(jar)
+    }
+
+    /// Saves every persistent cookie to a file, one per line. Session cookies are left out.
+    /// The file is written to a temporary path first so a crash can't leave it half-written,
+    /// and on Unix only the current user can read it.
+    pub fn save(&self, path: &Path) -> color_eyre::Result<()> {
+        let now = SystemTime::now();
+        let mut contents = String::from(
+            "# domain\thost_only\tpath\tsecure\thttp_only\tsame_site\texpires\tcreated\tname\tvalue\n",
+        );
+        for cookie in &self.cookies {
+            let Some(expires) = cookie.expires else {
+                continue;
+            };
+            if is_expired(cookie, now) {
+                continue;
+            }
+            contents.push_str(
+                &[
+                    cookie.domain.as_str(),
+                    bool_to_str(cookie.host_only),
+                    &cookie.path,
+                    bool_to_str(cookie.secure),
+                    bool_to_str(cookie.http_only),
+                    match cookie.same_site {
+                        SameSite::Strict => "strict",
+                        SameSite::Lax => "lax",
+                        SameSite::None => "none",
+                    },
+                    &unix_seconds(expires).to_string(),
+                    &unix_seconds(cookie.created).to_string(),
+                    &cookie.name,
+                    &cookie.value,
+                ]
+                .join("\t"),
+            );
+            contents.push('\n');
+        }
+
+        if let Some(parent) = path.parent() {
+            create_private_dir(parent)?;
+        }
+        // Saves from other browser processes, or other jars in this one, each get their own
+        // temp file.
+        static SAVE_COUNT: AtomicU64 = AtomicU64::new(0);
+        let temp_path = path.with_extension(format!(
+            "{}.{}.tmp",
+            std::process::id(),
+            SAVE_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = write_private_file(&temp_path, contents.as_bytes())
+            .and_then(|()| fs::rename(&temp_path, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        Ok(result?)
+    }
+}
+
+/// Parses a `Set-Cookie` header following RFC 6265 section 5.2, and checks the cookie against
+/// the URL it came from as in section 5.3.
+fn parse_set_cookie(url: &HttpUrl, header: &str, now: SystemTime) -> Option<Cookie> {
+    let (name_value, attributes) = header.split_once(';').unwrap_or((header, ""));
+    let (name, value) = match name_value.split_once('=') {
+        Some((name, value)) => (name.trim_ascii(), value.trim_ascii()),
+        None => ("", name_value.trim_ascii()),
+    };
+    if name.is_empty() && value.is_empty() {
+        return None;
+    }
+    if name
+        .chars()
+        .chain(value.chars())
+        .any(|c| c.is_ascii_control())
+    {
+        return None;
+    }
+
+    let mut expires = None;
+    let mut max_age = None;
+    let mut domain = None;
+    let mut path = None;
+    let mut secure = false;
+    let mut http_only = false;
+    let mut same_site = SameSite::Lax;
+    for attribute in attributes.split(';') {
+        let (attribute_name, attribute_value) =
+            attribute.split_once('=').unwrap_or((attribute, ""));
+        let attribute_value = attribute_value.trim_ascii();
+        match attribute_name.trim_ascii().to_ascii_lowercase().as_str() {
+            "expires" => {
+                if let Some(time) = parse_http_date(attribute_value) {
+                    expires = Some(time);
+                }
+            }
+            "max-age" => {
+                let digits = attribute_value.strip_prefix('-').unwrap_or(attribute_value);
+                if !digits.is_empty() && digits.bytes().all(|c| c.is_ascii_digit()) {
+                    // Huge values would overflow, but they all mean "practically forever".
+                    let seconds = attribute_value.parse::<i64>().unwrap_or(i64::MAX);
+                    max_age = Some(if seconds <= 0 {
+                        UNIX_EPOCH
+                    } else {
+                        now.checked_add(Duration::from_secs(seconds as u64))
+                            .unwrap_or(now + Duration::from_secs(u32::MAX as u64))
+                    });
+                }
+            }
+            "domain" => {
+                // An invalid domain means the attribute is ignored, not the whole cookie.
+                let value = attribute_value.strip_prefix('.').unwrap_or(attribute_value);
+                match idna::domain_to_ascii(value) {
+                    Ok(value) if !value.is_empty() => domain = Some(value),
+                    _ => {}
+                }
+            }
+            "path" => {
+                // Control characters, including tabs, would corrupt the saved cookie file.
+                path = (attribute_value.starts_with('/')
+                    && !attribute_value.chars().any(|c| c.is_control()))
+                .then(|| attribute_value.to_string());
+            }
+            "secure" => secure = true,
+            "httponly" => http_only = true,
+            "samesite" => match attribute_value.to_ascii_lowercase().as_str() {
+                "strict" => same_site = SameSite::Strict,
+                "lax" => same_site = SameSite::Lax,
+                "none" => same_site = SameSite::None,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    let (domain, host_only) = match domain {
+        // Sites can't set cookies for a whole public suffix like `co.uk`, unless the site is the
+        // public suffix itself, in which case the cookie is only for that host.
+        Some(domain) if public_suffix(&domain) == domain && domain == url.host => (domain, true),
+        Some(domain) if public_suffix(&domain) == domain => return None,
+        Some(domain) if domain_matches(&url.host, &domain) => (domain, false),
+        Some(_) => return None,
+        None => (url.host.clone(), true),
+    };
+    // Only secure origins can set secure cookies, and `SameSite=None` requires `Secure`.
+    if secure && !url.tls || same_site == SameSite::None && !secure {
+        return None;
+    }
+
+    Some(Cookie {
+        name: name.to_string(),
+        value: value.to_string(),
+        domain,
+        host_only,
+        path: path.unwrap_or_else(|| default_path(&url.path)),
+        expires: max_age.or(expires),
+        secure,
+        http_only,
+        same_site,
+        created: now,
+    })
+}
+
+fn parse_saved_cookie(line: &str) -> Option<Cookie> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [domain, host_only, path, secure, http_only, same_site, expires, created, name, value] =
+        fields[..]
+    else {
+        return None;
+    };
+    let from_unix_seconds = |seconds: &str| -> Option<SystemTime> {
+        Some(UNIX_EPOCH + Duration::from_secs(seconds.parse().ok()?))
+    };
+    Some(Cookie {
+        name: name.to_string(),
+        value: value.to_string(),
+        domain: domain.to_string(),
+        host_only: str_to_bool(host_only)?,
+        path: path.to_string(),
+        expires: Some(from_unix_seconds(expires)?),
+        secure: str_to_bool(secure)?,
+        http_only: str_to_bool(http_only)?,
+        same_site: match same_site {
+            "strict" => SameSite::Strict,
+            "lax" => SameSite::Lax,
+            "none" => SameSite::None,
+            _ => return None,
+        },
+        created: from_unix_seconds(created)?,
+    })
+}
+
+/// Creates a directory and its parents, with any new ones only accessible to the current user.
+pub(crate) fn create_private_dir(path: &Path) -> std::io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(path)
+}
+
+/// Creates a new file that only the current user can read, and writes it all to disk.
+pub(crate) fn write_private_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+fn is_expired(cookie: &Cookie, now: SystemTime) -> bool {
+    cookie.expires.is_some_and(|expires| expires <= now)
+}
+
+/// Whether `host` is `domain` or one of its subdomains. IP addresses only match themselves.
+fn domain_matches(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    let is_ip_address = host.starts_with('[') || host.parse::<std::net::Ipv4Addr>().is_ok();
+    !is_ip_address
+        && host
+            .strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.ends_with('.'))
+}
+
+/// Whether a cookie with the path `cookie_path` should be sent to `request_path`, e.g. `/docs`
+/// matches `/docs`, `/docs/` and `/docs/intro.html` but not `/docsearch`.
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/'))
+}
+
+/// The path a cookie applies to when it has no `Path` attribute: the "directory" of the URL.
+fn default_path(url_path: &str) -> String {
+    match url_path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => url_path[..index].to_string(),
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+fn bool_to_str(value: bool) -> &'static str {
+    if value {
+        "true"
+    } else {
+        "false"
+    }
+}
+
+fn str_to_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::testing::http_url;
+
+    fn jar_with(url: &str, headers: &[&str]) -> CookieJar {
+        let mut jar = CookieJar::new();
+        for header in headers {
+            jar.set_cookie(&http_url(url), header);
+        }
+        jar
+    }
+
+    fn cookie_header(jar: &CookieJar, url: &str) -> Option<String> {
+        jar.cookie_header(&http_url(url))
+    }
+
+    #[test]
+    fn host_only_cookie() {
+        let jar = jar_with("http://example.org/", &["session=abc123"]);
+        assert_eq!(
+            cookie_header(&jar, "http://example.org/anything"),
+            Some("session=abc123".to_string())
+        );
+        assert_eq!(cookie_header(&jar, "http://www.example.org/"), None);
+        assert_eq!(cookie_header(&jar, "http://other.org/"), None);
+    }
+
+    #[test]
+    fn domain_cookie() {
+        let jar = jar_with("http://www.example.org/", &["a=1; Domain=.Example.org"]);
+        assert_eq!(jar.cookies()[0].domain, "example.org");
+        assert!(!jar.cookies()[0].host_only);
+        for url in ["http://example.org/", "http://a.b.example.org/"] {
+            assert_eq!(cookie_header(&jar, url), Some("a=1".to_string()), "{url}");
+        }
+        assert_eq!(cookie_header(&jar, "http://notexample.org/"), None);
+    }
+
+    #[test]
+    fn oldest_cookies_are_evicted() {
+        let start = SystemTime::now();
+        let mut jar = CookieJar::new();
+        for i in 0..=MAX_COOKIES_PER_DOMAIN {
+            let now = start + Duration::from_secs(i as u64);
+            jar.set_cookie_at(&http_url("http://example.org/"), &format!("c{i}=1"), now);
+        }
+        assert_eq!(jar.cookies().len(), MAX_COOKIES_PER_DOMAIN);
+        assert!(jar.cookies().iter().all(|cookie| cookie.name != "c0"));
+
+        // Other domains count towards the total.
+        for i in 0..MAX_COOKIES / MAX_COOKIES_PER_DOMAIN {
+            let url = http_url(&format!("http://site{i}.example/"));
+            for j in 0..MAX_COOKIES_PER_DOMAIN {
+                jar.set_cookie_at(&url, &format!("c{j}=1"), start + Duration::from_secs(100));
+            }
+        }
+        assert_eq!(jar.cookies().len(), MAX_COOKIES);
+        assert!(jar
+            .cookies()
+            .iter()
+            .all(|cookie| cookie.domain != "example.org"));
+    }
+
+    #[test]
+    fn domain_must_match_host() {
+        let jar = jar_with("http://example.org/", &["a=1; Domain=other.org"]);
+        assert!(jar.cookies().is_empty());
+        let jar = jar_with("http://example.org/", &["a=1; Domain=www.example.org"]);
+        assert!(jar.cookies().is_empty());
+    }
+
+    #[test]
+    fn domain_cannot_be_public_suffix() {
+        let jar = jar_with("http://example.co.uk/", &["a=1; Domain=co.uk"]);
+        assert!(jar.cookies().is_empty());
+        let jar = jar_with("http://alice.github.io/", &["a=1; Domain=github.io"]);
+        assert!(jar.cookies().is_empty());
+    }
+
+    #[test]
+    fn domain_equal_to_host_includes_subdomains() {
+        let jar = jar_with("http://example.org/", &["a=1; Domain=example.org"]);
+        assert!(!jar.cookies()[0].host_only);
+        assert_eq!(
+            cookie_header(&jar, "http://www.example.org/"),
+            Some("a=1".to_string())
+        );
+    }
+
+    #[test]
+    fn domain_equal_to_public_suffix_host_is_host_only() {
+        let jar = jar_with("http://github.io/", &["a=1; Domain=github.io"]);
+        assert!(jar.cookies()[0].host_only);
+        assert_eq!(cookie_header(&jar, "http://alice.github.io/"), None);
+    }
+
+    #[test]
+    fn domain_on_ip_address_only_matches_itself() {
+        let jar = jar_with("http://127.0.0.1/", &["a=1; Domain=127.0.0.1"]);
+        assert_eq!(
+            cookie_header(&jar, "http://127.0.0.1/"),
+            Some("a=1".to_string())
+        );
+        let jar = jar_with("http://127.0.0.1/", &["a=1; Domain=0.0.1"]);
+        assert!(jar.cookies().is_empty());
+    }
+
+    #[test]
+    fn invalid_domain_attribute_is_ignored() {
+        let jar = jar_with(
+            "http://example.org/",
+            &["a=1; Domain=xn--a.org", "b=2; Domain=."],
+        );
+        assert_eq!(jar.cookies().len(), 2);
+        assert!(jar.cookies().iter().all(|cookie| cookie.host_only));
+    }
+
+    #[test]
+    fn path_attribute_and_default_path() {
+        let jar = jar_with(
+            "http://example.org/docs/guide/intro.html",
+            &[
+                "default=1",
+                "root=2; Path=/",
+                "docs=3; path=/docs",
+                "bad=4; Path=docs",
+                "tab=5; Path=/docs\tevil",
+            ],
+        );
+        let paths: Vec<_> = jar
+            .cookies()
+            .iter()
+            .map(|cookie| (cookie.name.as_str(), cookie.path.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("default", "/docs/guide"),
+                ("root", "/"),
+                ("docs", "/docs"),
+                ("bad", "/docs/guide"),
+                ("tab", "/docs/guide")
+            ]
+        );
+
+        assert_eq!(
+            cookie_header(&jar, "http://example.org/docs/guide/next.html"),
+            Some("default=1; bad=4; tab=5; docs=3; root=2".to_string())
+        );
+        assert_eq!(
+            cookie_header(&jar, "http://example.org/docs"),
+            Some("docs=3; root=2".to_string())
+        );
+        assert_eq!(
+            cookie_header(&jar, "http://example.org/docsearch"),
+            Some("root=2".to_string())
+        );
+    }
+
+    #[test]
+    fn replacing_and_deleting_cookies() {
+        let mut jar = jar_with("http://example.org/", &["a=1", "b=2"]);
+        jar.set_cookie(&http_url("http://example.org/"), "a=3");
+        assert_eq!(
+            cookie_header(&jar, "http://example.org/"),
+            Some("a=3; b=2".to_string())
+        );
+
+        jar.set_cookie(&http_url("http://example.org/"), "a=; Max-Age=0");
+        jar.set_cookie(
+            &http_url("http://example.org/"),
+            "b=; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+        );
+        assert_eq!(cookie_header(&jar, "http://example.org/"), None);
+    }
+
+    #[test]
+    fn expires_and_max_age() {
+        let url = http_url("http://example.org/");
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let mut jar = CookieJar::new();
+        jar.set_cookie_at(&url, "a=1; Expires=Wed, 09 Jun 2100 10:18:14 GMT", now);
+        jar.set_cookie_at(
+            &url,
+            "b=2; Max-Age=60; Expires=Wed, 09 Jun 2100 10:18:14 GMT",
+            now,
+        );
+        jar.set_cookie_at(&url, "c=3; Max-Age=soon", now);
+
+        assert_eq!(
+            jar.cookies()[0].expires,
+            parse_http_date("09 Jun 2100 10:18:14")
+        );
+        assert_eq!(
+            jar.cookies()[1].expires,
+            Some(now + Duration::from_secs(60))
+        );
+        assert_eq!(jar.cookies()[2].expires, None);
+
+        assert_eq!(
+            jar.cookie_header_at(&url, now + Duration::from_secs(59)),
+            Some("a=1; b=2; c=3".to_string())
+        );
+        assert_eq!(
+            jar.cookie_header_at(&url, now + Duration::from_secs(60)),
+            Some("a=1; c=3".to_string())
+        );
+    }
+
+    #[test]
+    fn secure_cookies() {
+        let jar = jar_with("https://example.org/", &["a=1; Secure", "b=2"]);
+        assert_eq!(
+            cookie_header(&jar, "https://example.org/"),
+            Some("a=1; b=2".to_string())
+        );
+        assert_eq!(
+            cookie_header(&jar, "http://example.org/"),
+            Some("b=2".to_string())
+        );
+
+        let jar = jar_with("http://example.org/", &["a=1; Secure"]);
+        assert!(jar.cookies().is_empty());
+    }
+
+    #[test]
+    fn flags_and_same_site() {
+        let jar = jar_with(
+            "https://example.org/",
+            &[
+                "a=1; HttpOnly; SameSite=Strict",
+                "b=2; SameSite=None; Secure",
+                "c=3; SameSite=None",
+                "d=4; SameSite=bogus",
+            ],
+        );
+        let cookies = jar.cookies();
+        assert_eq!(cookies.len(), 3);
+        assert!(cookies[0].http_only);
+        assert_eq!(cookies[0].same_site, SameSite::Strict);
+        assert_eq!(cookies[1].same_site, SameSite::None);
+        assert_eq!(cookies[2].name, "d");
+        assert_eq!(cookies[2].same_site, SameSite::Lax);
+    }
+
+    #[test]
+    fn invalid_cookies_are_ignored() {
+        let jar = jar_with("http://example.org/", &["", "=", "; Path=/", "a=b\u{7}c"]);
+        assert!(jar.cookies().is_empty());
+
+        let jar = jar_with("http://example.org/", &["justavalue"]);
+        assert_eq!(
+            cookie_header(&jar, "http://example.org/"),
+            Some("justavalue".to_string())
+        );
+    }
+
+    #[test]
+    fn store_response_cookies_reads_every_header() {
+        let mut headers = Headers::new();
+        headers.append("Set-Cookie", "a=1");
+        headers.append("Set-Cookie", "b=2; Path=/");
+        let mut jar = CookieJar::new();
+        jar.store_response_cookies(&http_url("http://example.org/"), &headers);
+        assert_eq!(
+            cookie_header(&jar, "http://example.org/"),
+            Some("a=1; b=2".to_string())
+        );
+    }
+
+    #[test]
+    fn save_and_load() -> color_eyre::Result<()> {
+        let dir = std::env::temp_dir().join(format!("bowsernet-cookies-{}", std::process::id()));
+        let path = dir.join("cookies.txt");
+
+        let jar = jar_with(
+            "https://www.example.org/",
+            &[
+                "persistent=1; Domain=example.org; Path=/docs; Secure; HttpOnly; SameSite=Strict; Max-Age=3600",
+                "session=2",
+            ],
+        );
+        jar.save(&path)?;
+        #[cfg(unix)]
+        let mode = std::os::unix::fs::PermissionsExt::mode(&fs::metadata(&path)?.permissions());
+        let loaded = CookieJar::load(&path)?;
+        let files = fs::read_dir(&dir)?.count();
+        fs::remove_dir_all(&dir)?;
+
+        #[cfg(unix)]
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(files, 1, "temp file left behind");
+
+        assert_eq!(loaded.cookies().len(), 1);
+        let (saved, loaded) = (&jar.cookies()[0], &loaded.cookies()[0]);
+        assert_eq!(loaded.name, "persistent");
+        assert_eq!(loaded.domain, saved.domain);
+        assert_eq!(loaded.host_only, saved.host_only);
+        assert_eq!(loaded.path, saved.path);
+        assert!(loaded.secure && loaded.http_only);
+        assert_eq!(loaded.same_site, SameSite::Strict);
+        assert_eq!(
+            loaded.expires.map(unix_seconds),
+            saved.expires.map(unix_seconds)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_missing_file() -> color_eyre::Result<()> {
+        let jar = CookieJar::load(Path::new("/nonexistent/bowsernet/cookies.txt"))?;
+        assert!(jar.cookies().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Parses a date as found in `Expires`, `Date` and `Last-Modified` headers and cookie
+/// `Expires` attributes, using the lenient algorithm from RFC 6265 section 5.1.1. This accepts
+/// all three HTTP-date formats along with the many variations servers send in practice.
+pub fn parse_http_date(input: &str) -> Option<SystemTime> {
+    let mut time = None;
+    let mut day_of_month = None;
+    let mut month = None;
+    let mut year = None;
+
+    for token in input.split(is_delimiter).filter(|token| !token.is_empty()) {
+        if time.is_none() {
+            if let Some(parsed) = parse_time(token) {
+                time = Some(parsed);
+                continue;
+            }
+        }
+        if day_of_month.is_none() {
+            if let Some(parsed) = parse_digits(token, 1, 2) {
+                day_of_month = Some(parsed);
+                continue;
+            }
+        }
+        if month.is_none() {
+            let prefix = token.get(..3).map(|prefix| prefix.to_ascii_lowercase());
+            if let Some(index) = MONTHS.iter().position(|m| Some(*m) == prefix.as_deref()) {
+                month = Some(index as u64 + 1);
+                continue;
+            }
+        }
+        if year.is_none() {
+            if let Some(parsed) = parse_digits(token, 2, 4) {
+                year = Some(parsed);
+                continue;
+            }
+        }
+    }
+
+    let (hour, minute, second) = time?;
+    let (day_of_month, month, year) = (day_of_month?, month?, year?);
+    let year = match year {
+        70..=99 => year + 1900,
+        0..=69 => year + 2000,
+        _ => year,
+    };
+    if year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    if day_of_month < 1 || day_of_month > days_in_month(year, month) {
+        return None;
+    }
+
+    let days = days_from_civil(year as i64, month, day_of_month);
+    let seconds = days * 86400 + (hour * 3600 + minute * 60 + second) as i64;
+    if seconds >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(seconds as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(seconds.unsigned_abs()))
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    matches!(c, '\t' | ' '..='/' | ';'..='@' | '['..='`' | '{'..='~')
+}
+
+/// Parses a token starting with between `min` and `max` digits, followed by anything that
+/// isn't a digit.
+fn parse_digits(token: &str, min: usize, max: usize) -> Option<u64> {
+    let length = token
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(token.len());
+    if length < min || length > max {
+        return None;
+    }
+    token[..length].parse().ok()
+}
+
+/// Parses a `hh:mm:ss` token, where each part can be one or two digits.
+fn parse_time(token: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = token.splitn(3, ':');
+    let hour = parts.next()?;
+    let minute = parts.next()?;
+    let second = parts.next()?;
+    let all_digits =
+        |part: &str| (1..=2).contains(&part.len()) && part.bytes().all(|c| c.is_ascii_digit());
+    if !all_digits(hour) || !all_digits(minute) {
+        return None;
+    }
+    Some((
+        hour.parse().ok()?,
+        minute.parse().ok()?,
+        parse_digits(second, 1, 2)?,
+    ))
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a date in the proleptic Gregorian calendar, using Howard
+/// Hinnant's `days_from_civil` algorithm.
+fn days_from_civil(year: i64, month: u64, day: u64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unix(seconds: u64) -> Option<SystemTime> {
+        Some(UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    #[test]
+    fn parse_http_date_formats() {
+        for input in [
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            "sun, 6 nov 1994 8:49:37 gmt",
+            "Sun, 06-Nov-1994 08:49:37 UTC",
+        ] {
+            assert_eq!(parse_http_date(input), unix(784111777), "{input}");
+        }
+    }
+
+    #[test]
+    fn parse_http_date_two_digit_years() {
+        assert_eq!(parse_http_date("01 Jan 70 00:00:00"), unix(0));
+        assert_eq!(
+            parse_http_date("01 Jan 69 00:00:00"),
+            parse_http_date("01 Jan 2069 00:00:00")
+        );
+    }
+
+    #[test]
+    fn parse_http_date_before_epoch() {
+        assert_eq!(
+            parse_http_date("Thu, 31 Dec 1969 23:59:59 GMT"),
+            UNIX_EPOCH.checked_sub(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn parse_http_date_leap_day() {
+        assert_eq!(parse_http_date("29 Feb 2000 00:00:00"), unix(951782400));
+        assert_eq!(parse_http_date("29 Feb 1900 00:00:00"), None);
+    }
+
+    #[test]
+    fn parse_http_date_invalid() {
+        for input in [
+            "",
+            "0",
+            "tomorrow",
+            "Sun, 06 Nov 1994",
+            "Sun, 32 Nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1600 08:49:37 GMT",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+        ] {
+            assert_eq!(parse_http_date(input), None, "{input}");
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::{fmt::Display, io::ErrorKind};
+
+/// Why we couldn't get a usable response from a server, as opposed to the server responding
+/// with an error status. [`Browser`](crate::Browser) shows these as an error page instead of
+/// giving up.
+#[derive(Debug)]
+pub struct NetworkError {
+    pub kind: NetworkErrorKind,
+    /// The server we were trying to reach, as `scheme://host:port`.
+    pub server: String,
+    /// The underlying error, for showing to the user.
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkErrorKind {
+    /// The host name couldn't be resolved to an address.
+    Dns,
+    ConnectionRefused,
+    /// The TLS handshake failed, e.g. because of an invalid certificate.
+    Tls,
+    Timeout,
+    /// The server closed the connection without sending a complete response.
+    ConnectionClosed,
+    /// The server sent something that isn't a valid HTTP response.
+    InvalidResponse,
+    /// The server redirected somewhere that can't be followed, e.g. an invalid or local URL,
+    /// or in a loop.
+    InvalidRedirect,
+    Other,
+}
+
+impl NetworkErrorKind {
+    /// A short summary suitable for the heading of an error page.
+    pub fn title(&self) -> &'static str {
+        match self {
+            NetworkErrorKind::Dns => "Server not found",
+            NetworkErrorKind::ConnectionRefused => "Connection refused",
+            NetworkErrorKind::Tls => "Secure connection failed",
+            NetworkErrorKind::Timeout => "Connection timed out",
+            NetworkErrorKind::ConnectionClosed => "Connection closed",
+            NetworkErrorKind::InvalidResponse => "Invalid response",
+            NetworkErrorKind::InvalidRedirect => "Invalid redirect",
+            NetworkErrorKind::Other => "Connection failed",
+        }
+    }
+}
+
+impl NetworkError {
+    pub fn new(kind: NetworkErrorKind, server: &str, message: impl Display) -> Self {
+        Self {
+            kind,
+            server: server.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Works out what kind of failure an I/O error on a connection was. TLS failures surface
+    /// as I/O errors wrapping a [`rustls::Error`], since the handshake happens on first use.
+    pub fn from_io(server: &str, error: std::io::Error) -> Self {
+        let kind = if error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<rustls::Error>())
+        {
+            NetworkErrorKind::Tls
+        } else {
+            match error.kind() {
+                ErrorKind::ConnectionRefused => NetworkErrorKind::ConnectionRefused,
+                ErrorKind::TimedOut | ErrorKind::WouldBlock => NetworkErrorKind::Timeout,
+                ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe
+                | ErrorKind::UnexpectedEof => NetworkErrorKind::ConnectionClosed,
+                _ => NetworkErrorKind::Other,
+            }
+        };
+        Self::new(kind, server, error)
+    }
+}
+
+impl Display for NetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}): {}",
+            self.kind.title(),
+            self.server,
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for NetworkError {}
@@ -1,21 +1,13 @@
-use std::collections::HashMap;
-
-#[derive(Debug)]
+/// HTTP header fields in the order they were added. Names are matched case-insensitively but
+/// keep their original case when serialized, and a name can appear more than once.
+#[derive(Debug, Clone, Default)]
 pub struct Headers {
-    values: HashMap<String, HeaderValue>,
-}
-
-#[derive(Debug)]
-struct HeaderValue {
-    original_name: String,
-    value: String,
+    fields: Vec<(String, String)>,
 }
 
 impl Headers {
     pub fn new() -> Self {
-        Self {
-            values: HashMap::new(),
-        }
+        Self { fields: Vec::new() }
     }
 
     pub fn add(mut self, name: &str, value: &str) -> Self {
@@ -23,55 +15,173 @@ impl Headers {
         self
     }
 
+    /// The last value of the header, if it was given more than once.
     pub fn get(&self, name: &str) -> Option<&str> {
-        self.values
-            .get(&name.to_ascii_lowercase())
-            .map(|value| value.value.as_str())
+        self.get_all(name).pop()
     }
 
+    /// Every value of the header, in order.
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.fields
+            .iter()
+            .filter(|(field_name, _)| field_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Every value of the header joined with commas, which RFC 9110 says means the same as
+    /// sending them separately for list-based headers like `Cache-Control` and `Vary`. Don't
+    /// use this for `Set-Cookie`, whose values can contain commas.
+    pub fn get_combined(&self, name: &str) -> Option<String> {
+        let values = self.get_all(name);
+        (!values.is_empty()).then(|| values.join(", "))
+    }
+
+    /// Sets a header, replacing every existing value. The header keeps its original position
+    /// if it was already present.
     pub fn set(&mut self, name: &str, value: &str) {
-        self.values.insert(
-            name.to_ascii_lowercase(),
-            HeaderValue {
-                original_name: name.to_string(),
-                value: value.to_string(),
-            },
-        );
+        match self.position(name) {
+            Some(index) => {
+                self.fields[index] = (name.to_string(), value.to_string());
+                let rest = self.fields.split_off(index + 1);
+                self.fields.extend(
+                    rest.into_iter()
+                        .filter(|(field_name, _)| !field_name.eq_ignore_ascii_case(name)),
+                );
+            }
+            None => self.fields.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Adds a value for the header after all the existing headers, keeping any existing values.
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.fields.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.fields
+            .retain(|(field_name, _)| !field_name.eq_ignore_ascii_case(name));
     }
 
     pub fn contains(&self, name: &str) -> bool {
-        self.values.contains_key(&name.to_ascii_lowercase())
+        self.position(name).is_some()
+    }
+
+    /// Every header name and value, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
     }
 
     pub fn to_http_string(&self) -> String {
         let mut s = String::new();
-        for header in self.values.values() {
-            s.push_str(&format!("{}: {}\r\n", header.original_name, header.value));
+        for (name, value) in &self.fields {
+            s.push_str(&format!("{}: {}\r\n", name, value));
         }
         s
     }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.fields
+            .iter()
+            .position(|(field_name, _)| field_name.eq_ignore_ascii_case(name))
+    }
 }
 
-#[derive(Debug, Default)]
+/// The `Cache-Control` directives of a response that we understand, from RFC 9111 section
+/// 5.2.2. Unknown directives are ignored.
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct CacheControl {
     pub no_store: bool,
+    /// The response can be stored, but has to be revalidated every time it's used.
+    pub no_cache: bool,
+    /// The response can't be used once it's stale, even if the server is unreachable.
+    pub must_revalidate: bool,
+    pub public: bool,
+    pub private: bool,
     pub max_age: Option<u64>,
+    /// Like `max-age`, but only for shared caches, so we don't use it for freshness.
+    pub s_maxage: Option<u64>,
 }
 
 impl From<&str> for CacheControl {
     fn from(value: &str) -> Self {
-        let mut no_store = false;
-        let mut max_age = None;
-        for directive in value.split(',') {
-            let directive = directive.trim_ascii().to_lowercase();
-            if directive == "no-store" {
-                no_store = true;
-            } else if let Some(max_age_value) = directive.strip_prefix("max-age=") {
-                max_age = max_age_value.parse().ok().filter(|&max_age| max_age != 0);
+        let mut cache_control = CacheControl::default();
+        for (name, argument) in directives(value) {
+            // An invalid number of seconds means the response should be treated as stale.
+            let seconds = || {
+                argument
+                    .as_deref()
+                    .and_then(|seconds| seconds.parse().ok())
+                    .or(Some(0))
+            };
+            // `no-cache` and `private` can list specific fields they apply to, but applying
+            // them to the whole response is always allowed. A repeated `max-age` or `s-maxage`
+            // makes the response stale, as RFC 9111 suggests.
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => cache_control.no_store = true,
+                "no-cache" => cache_control.no_cache = true,
+                "must-revalidate" => cache_control.must_revalidate = true,
+                "public" => cache_control.public = true,
+                "private" => cache_control.private = true,
+                "max-age" if cache_control.max_age.is_some() => cache_control.max_age = Some(0),
+                "max-age" => cache_control.max_age = seconds(),
+                "s-maxage" if cache_control.s_maxage.is_some() => {
+                    cache_control.s_maxage = Some(0);
+                }
+                "s-maxage" => cache_control.s_maxage = seconds(),
+                _ => {}
             }
         }
+        cache_control
+    }
+}
 
-        CacheControl { no_store, max_age }
+/// Splits a list of directives like `max-age=60, no-cache="Set-Cookie, Link"` into names and
+/// arguments. Commas in quoted strings don't separate directives, and quoted arguments are
+/// unescaped.
+fn directives(value: &str) -> Vec<(&str, Option<String>)> {
+    let mut directives = Vec::new();
+    let mut rest = value;
+    loop {
+        let name_end = rest.find([',', '=']).unwrap_or(rest.len());
+        let name = rest[..name_end].trim_ascii();
+        rest = &rest[name_end..];
+
+        let argument = rest.strip_prefix('=').map(|argument| {
+            let argument = argument.trim_ascii_start();
+            if let Some(quoted) = argument.strip_prefix('"') {
+                let mut unescaped = String::new();
+                let mut chars = quoted.char_indices();
+                // An unterminated string runs to the end of the value.
+                rest = "";
+                while let Some((index, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            rest = &quoted[index + 1..];
+                            break;
+                        }
+                        '\\' => unescaped.extend(chars.next().map(|(_, c)| c)),
+                        c => unescaped.push(c),
+                    }
+                }
+                unescaped
+            } else {
+                let end = argument.find(',').unwrap_or(argument.len());
+                rest = &argument[end..];
+                argument[..end].trim_ascii().to_string()
+            }
+        });
+        if !name.is_empty() {
+            directives.push((name, argument));
+        }
+
+        // Anything between a quoted string and the next comma is ignored.
+        match rest.find(',') {
+            Some(comma) => rest = &rest[comma + 1..],
+            None => return directives,
+        }
     }
 }
 
@@ -98,17 +208,152 @@ mod tests {
         assert_eq!(Some("example.com"), headers.get("host"));
     }
 
+    #[test]
+    fn headers_append_keeps_every_value() {
+        let mut headers = Headers::new();
+        headers.append("Set-Cookie", "a=1");
+        headers.append("set-cookie", "b=2");
+
+        assert_eq!(vec!["a=1", "b=2"], headers.get_all("Set-Cookie"));
+        assert_eq!(Some("b=2"), headers.get("Set-Cookie"));
+        assert_eq!(
+            "Set-Cookie: a=1\r\nset-cookie: b=2\r\n",
+            headers.to_http_string()
+        );
+        assert!(headers.get_all("Cookie").is_empty());
+    }
+
     #[test]
     fn headers_to_http_string() {
         let headers = Headers::new()
             .add("Host", "example.org")
             .add("Connection", "close");
 
-        let http_string = headers.to_http_string();
+        assert_eq!(
+            "Host: example.org\r\nConnection: close\r\n",
+            headers.to_http_string()
+        );
+    }
 
-        assert!(
-            http_string == "Host: example.org\r\nConnection: close\r\n"
-                || http_string == "Connection: close\r\nHost: example.org\r\n"
+    #[test]
+    fn headers_keep_insertion_order() {
+        let mut headers = Headers::new();
+        headers.append("Vary", "Accept");
+        headers.append("Link", "</style.css>; rel=preload");
+        headers.append("vary", "Accept-Encoding");
+        headers.set("Content-Type", "text/html");
+
+        let names: Vec<_> = headers.iter().map(|(name, _)| name).collect();
+        assert_eq!(vec!["Vary", "Link", "vary", "Content-Type"], names);
+    }
+
+    #[test]
+    fn headers_set_replaces_in_place() {
+        let mut headers = Headers::new();
+        headers.append("Accept", "text/html");
+        headers.append("Host", "example.org");
+        headers.append("accept", "text/plain");
+        headers.set("ACCEPT", "*/*");
+
+        assert_eq!(
+            "ACCEPT: */*\r\nHost: example.org\r\n",
+            headers.to_http_string()
+        );
+    }
+
+    #[test]
+    fn headers_get_combined() {
+        let mut headers = Headers::new();
+        headers.append("Cache-Control", "no-cache");
+        headers.append("cache-control", "max-age=60");
+
+        assert_eq!(
+            Some("no-cache, max-age=60".to_string()),
+            headers.get_combined("Cache-Control")
         );
+        assert_eq!(None, headers.get_combined("Vary"));
+    }
+
+    #[test]
+    fn headers_remove() {
+        let mut headers = Headers::new()
+            .add("Host", "example.org")
+            .add("Cookie", "a=1");
+        headers.append("cookie", "b=2");
+        headers.remove("COOKIE");
+
+        assert!(!headers.contains("cookie"));
+        assert_eq!("Host: example.org\r\n", headers.to_http_string());
+    }
+
+    #[test]
+    fn cache_control_directives() {
+        assert_eq!(
+            CacheControl::from("no-store, No-Cache, MUST-REVALIDATE, public, private"),
+            CacheControl {
+                no_store: true,
+                no_cache: true,
+                must_revalidate: true,
+                public: true,
+                private: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            CacheControl::from("private=\"Set-Cookie\", no-cache=\"Set-Cookie, Link\""),
+            CacheControl {
+                no_cache: true,
+                private: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            CacheControl::from("private=\"public, max-age=60\", no-store"),
+            CacheControl {
+                private: true,
+                no_store: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            CacheControl::from("no-cache=\"a\\\", public\", private"),
+            CacheControl {
+                no_cache: true,
+                private: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            CacheControl::from("no-cache=\"unterminated, public"),
+            CacheControl {
+                no_cache: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(CacheControl::from(""), CacheControl::default());
+        assert_eq!(
+            CacheControl::from("immutable, stale-while-revalidate=60"),
+            CacheControl::default()
+        );
+    }
+
+    #[test]
+    fn cache_control_ages() {
+        for (value, max_age, s_maxage) in [
+            ("max-age=60", Some(60), None),
+            ("max-age=\"60\"", Some(60), None),
+            (" Max-Age = 60 ", Some(60), None),
+            ("max-age=0", Some(0), None),
+            ("max-age=60, max-age=3600", Some(0), None),
+            ("s-maxage=60, s-maxage=60", None, Some(0)),
+            ("s-maxage=3600, max-age=60", Some(60), Some(3600)),
+            ("max-age=soon", Some(0), None),
+            ("max-age=-1", Some(0), None),
+            ("max-age", Some(0), None),
+        ] {
+            let cache_control = CacheControl::from(value);
+            assert_eq!(cache_control.max_age, max_age, "{value}");
+            assert_eq!(cache_control.s_maxage, s_maxage, "{value}");
+        }
     }
 }
@@ -0,0 +1,757 @@
+use std::{
+    fmt::Display,
+    io::{self, BufRead, ErrorKind, Read},
+};
+
+use crate::http::{Headers, Method};
+
+/// The longest status line, header line or chunk size line we accept, not counting the line
+/// ending.
+pub const MAX_LINE_LENGTH: usize = 8 * 1024;
+/// The most header fields we accept in a response head, and separately in a chunked trailer.
+pub const MAX_HEADERS: usize = 100;
+
+/// Why a response from a server couldn't be parsed. Anything a server sends ends up as one of
+/// these rather than a panic.
+#[derive(Debug)]
+pub enum ParseError {
+    InvalidStatusLine(String),
+    /// A status line for a version other than HTTP/1.x.
+    UnsupportedVersion(String),
+    InvalidStatusCode(String),
+    InvalidHeader(String),
+    /// A header line starting with whitespace, continuing the previous one. RFC 9112 deprecates
+    /// this, and it can hide headers from software that doesn't unfold lines the same way.
+    ObsoleteLineFolding,
+    LineTooLong,
+    TooManyHeaders,
+    InvalidContentLength(String),
+    InvalidChunkSize(String),
+    InvalidChunkExtension(String),
+    /// Chunk data not followed by CRLF, so the chunk size was wrong.
+    MissingChunkTerminator,
+    Io(io::Error),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::InvalidStatusLine(line) => write!(f, "Invalid status line '{line}'"),
+            ParseError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported HTTP version '{version}'")
+            }
+            ParseError::InvalidStatusCode(status) => write!(f, "Invalid status code '{status}'"),
+            ParseError::InvalidHeader(line) => write!(f, "Invalid header line '{line}'"),
+            ParseError::ObsoleteLineFolding => write!(f, "Header uses obsolete line folding"),
+            ParseError::LineTooLong => write!(f, "Line longer than {MAX_LINE_LENGTH} bytes"),
+            ParseError::TooManyHeaders => write!(f, "More than {MAX_HEADERS} headers"),
+            ParseError::InvalidContentLength(content_length) => {
+                write!(f, "Invalid Content-Length: {content_length}")
+            }
+            ParseError::InvalidChunkSize(size) => write!(f, "Invalid chunk size '{size}'"),
+            ParseError::InvalidChunkExtension(extension) => {
+                write!(f, "Invalid chunk extension '{extension}'")
+            }
+            ParseError::MissingChunkTerminator => write!(f, "Chunk data not followed by CRLF"),
+            ParseError::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(error: io::Error) -> Self {
+        ParseError::Io(error)
+    }
+}
+
+/// The status line and headers of a response.
+#[derive(Debug)]
+pub struct ResponseHead {
+    pub version: String,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Headers,
+}
+
+/// How the end of a response body is found.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BodyLength {
+    Empty,
+    Chunked,
+    Fixed(usize),
+    /// The body continues until the server closes the connection.
+    UntilClose,
+}
+
+pub fn read_response_head(stream: &mut impl BufRead) -> Result<ResponseHead, ParseError> {
+    let mut line = Vec::new();
+    read_line(stream, &mut line)?;
+    let (version, status, reason) = parse_status_line(&line)?;
+    let mut headers = Headers::new();
+    read_fields(stream, &mut line, &mut headers)?;
+
+    Ok(ResponseHead {
+        version,
+        status,
+        reason,
+        headers,
+    })
+}
+
+/// Works out how long the body of a response is, following the rules in RFC 9112 section 6.3.
+pub fn body_length(
+    method: Method,
+    status: u16,
+    headers: &Headers,
+) -> Result<BodyLength, ParseError> {
+    // Responses to HEAD requests have headers describing a body, but never a body.
+    if method == Method::Head || (100..=199).contains(&status) || status == 204 || status == 304 {
+        return Ok(BodyLength::Empty);
+    }
+
+    if let Some(transfer_encoding) = headers.get_combined("transfer-encoding") {
+        let is_chunked = transfer_encoding
+            .rsplit(',')
+            .next()
+            .is_some_and(|last| last.trim_ascii().eq_ignore_ascii_case("chunked"));
+        return Ok(if is_chunked {
+            BodyLength::Chunked
+        } else {
+            BodyLength::UntilClose
+        });
+    }
+
+    if let Some(content_length) = headers.get_combined("content-length") {
+        // Repeated values like `Content-Length: 5, 5` are allowed as long as they all agree.
+        let mut values = content_length.split(',').map(|value| value.trim_ascii());
+        let first = values.next().unwrap_or_default();
+        let is_valid = !first.is_empty()
+            && first.bytes().all(|c| c.is_ascii_digit())
+            && values.all(|value| value == first);
+        return match first.parse() {
+            Ok(length) if is_valid => Ok(BodyLength::Fixed(length)),
+            _ => Err(ParseError::InvalidContentLength(content_length)),
+        };
+    }
+
+    Ok(BodyLength::UntilClose)
+}
+
+pub fn read_body(
+    stream: &mut impl BufRead,
+    body_length: &BodyLength,
+) -> Result<Vec<u8>, ParseError> {
+    let mut content = Vec::new();
+    match *body_length {
+        BodyLength::Empty => {}
+        BodyLength::Chunked => read_chunked_body(stream, &mut content)?,
+        BodyLength::Fixed(length) => read_exact(stream, length, &mut content)?,
+        BodyLength::UntilClose => {
+            stream.read_to_end(&mut content)?;
+        }
+    }
+    Ok(content)
+}
+
+fn read_chunked_body(stream: &mut impl BufRead, content: &mut Vec<u8>) -> Result<(), ParseError> {
+    let mut line = Vec::new();
+    loop {
+        read_line(stream, &mut line)?;
+        let chunk_length = parse_chunk_size(&line)?;
+        if chunk_length == 0 {
+            break;
+        }
+
+        tracing::debug!("Reading chunk of length {chunk_length}");
+        read_exact(stream, chunk_length, content)?;
+
+        let mut terminator = Vec::with_capacity(2);
+        read_exact(stream, 2, &mut terminator)?;
+        if terminator != b"\r\n" {
+            return Err(ParseError::MissingChunkTerminator);
+        }
+    }
+
+    // Trailer fields aren't merged into the headers, since RFC 9110 only allows that for fields
+    // known to be safe in a trailer, and we don't use any of those.
+    let mut trailers = Headers::new();
+    read_fields(stream, &mut line, &mut trailers)?;
+    if trailers.iter().next().is_some() {
+        tracing::debug!("Ignoring trailers: {:?}", &trailers);
+    }
+    Ok(())
+}
+
+/// Appends exactly `length` bytes to `content`. The buffer grows as data arrives rather than
+/// being allocated up front, so a huge length from a server can't exhaust memory by itself.
+fn read_exact(
+    stream: &mut impl BufRead,
+    length: usize,
+    content: &mut Vec<u8>,
+) -> Result<(), ParseError> {
+    let read = stream.by_ref().take(length as u64).read_to_end(content)?;
+    if read < length {
+        return Err(unexpected_eof());
+    }
+    Ok(())
+}
+
+/// Reads a line ending in CRLF, or a bare LF which RFC 9112 allows recipients to accept, into
+/// `line` without its line ending.
+fn read_line(stream: &mut impl BufRead, line: &mut Vec<u8>) -> Result<(), ParseError> {
+    line.clear();
+    loop {
+        let available = stream.fill_buf()?;
+        if available.is_empty() {
+            return Err(unexpected_eof());
+        }
+        let newline = available.iter().position(|&c| c == b'\n');
+        let used = newline.map_or(available.len(), |index| index + 1);
+        line.extend_from_slice(&available[..used]);
+        stream.consume(used);
+
+        if newline.is_some() {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            if line.len() > MAX_LINE_LENGTH {
+                return Err(ParseError::LineTooLong);
+            }
+            return Ok(());
+        }
+        // Leave room for a CRLF that hasn't arrived yet.
+        if line.len() > MAX_LINE_LENGTH + 1 {
+            return Err(ParseError::LineTooLong);
+        }
+    }
+}
+
+/// Reads header fields up to the empty line that ends them.
+fn read_fields(
+    stream: &mut impl BufRead,
+    line: &mut Vec<u8>,
+    headers: &mut Headers,
+) -> Result<(), ParseError> {
+    let mut count = 0;
+    loop {
+        read_line(stream, line)?;
+        if line.is_empty() {
+            return Ok(());
+        }
+        if line[0] == b' ' || line[0] == b'\t' {
+            return Err(ParseError::ObsoleteLineFolding);
+        }
+        count += 1;
+        if count > MAX_HEADERS {
+            return Err(ParseError::TooManyHeaders);
+        }
+        let (name, value) = parse_field_line(line)?;
+        headers.append(&name, &value);
+    }
+}
+
+fn parse_status_line(line: &[u8]) -> Result<(String, u16, String), ParseError> {
+    let invalid = || ParseError::InvalidStatusLine(String::from_utf8_lossy(line).into_owned());
+
+    let mut parts = line.splitn(3, |&c| c == b' ');
+    let version = parts.next().unwrap_or_default();
+    let is_version = matches!(
+        version,
+        [b'H', b'T', b'T', b'P', b'/', major, b'.', minor]
+            if major.is_ascii_digit() && minor.is_ascii_digit()
+    );
+    if !is_version {
+        return Err(invalid());
+    }
+    if version[5] != b'1' {
+        return Err(ParseError::UnsupportedVersion(
+            String::from_utf8_lossy(version).into_owned(),
+        ));
+    }
+
+    let status = parts.next().ok_or_else(invalid)?;
+    let status_string = || String::from_utf8_lossy(status).into_owned();
+    if status.len() != 3 || !status.iter().all(u8::is_ascii_digit) {
+        return Err(ParseError::InvalidStatusCode(status_string()));
+    }
+    let status = status_string()
+        .parse()
+        .ok()
+        .filter(|status| (100..=599).contains(status))
+        .ok_or_else(|| ParseError::InvalidStatusCode(status_string()))?;
+
+    // The reason phrase is optional, and servers often leave out the space before it too.
+    let reason = parts.next().unwrap_or_default();
+    if reason.iter().any(|&c| is_control(c)) {
+        return Err(invalid());
+    }
+
+    Ok((
+        String::from_utf8_lossy(version).into_owned(),
+        status,
+        String::from_utf8_lossy(reason).into_owned(),
+    ))
+}
+
+fn parse_field_line(line: &[u8]) -> Result<(String, String), ParseError> {
+    let invalid = || ParseError::InvalidHeader(String::from_utf8_lossy(line).into_owned());
+
+    let colon = line.iter().position(|&c| c == b':').ok_or_else(invalid)?;
+    let (name, value) = (&line[..colon], &line[colon + 1..]);
+    // This also rejects whitespace before the colon, which RFC 9112 requires since servers and
+    // proxies disagreeing about it has been used to smuggle headers.
+    if !is_token(name) {
+        return Err(invalid());
+    }
+    let value = value.trim_ascii();
+    if value.iter().any(|&c| is_control(c)) {
+        return Err(invalid());
+    }
+
+    Ok((
+        String::from_utf8_lossy(name).into_owned(),
+        String::from_utf8_lossy(value).into_owned(),
+    ))
+}
+
+/// Parses a chunk size line, which may have extensions like `1a;name=value` that we ignore.
+fn parse_chunk_size(line: &[u8]) -> Result<usize, ParseError> {
+    let lossy = || String::from_utf8_lossy(line).into_owned();
+
+    let (size, extensions) = match line.iter().position(|&c| c == b';') {
+        Some(index) => (&line[..index], Some(&line[index + 1..])),
+        None => (line, None),
+    };
+    let size = size.trim_ascii_end();
+    if size.is_empty() || !size.iter().all(u8::is_ascii_hexdigit) {
+        return Err(ParseError::InvalidChunkSize(lossy()));
+    }
+    let size = std::str::from_utf8(size)
+        .ok()
+        .and_then(|size| usize::from_str_radix(size, 16).ok())
+        .ok_or_else(|| ParseError::InvalidChunkSize(lossy()))?;
+
+    if let Some(extensions) = extensions {
+        for extension in extensions.split(|&c| c == b';') {
+            let name = extension
+                .split(|&c| c == b'=')
+                .next()
+                .unwrap_or_default()
+                .trim_ascii();
+            if !is_token(name) || extension.iter().any(|&c| is_control(c)) {
+                return Err(ParseError::InvalidChunkExtension(lossy()));
+            }
+        }
+    }
+
+    Ok(size)
+}
+
+fn is_token(s: &[u8]) -> bool {
+    !s.is_empty()
+        && s.iter()
+            .all(|&c| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c))
+}
+
+/// Control characters other than tab, which can't appear in header values or reason phrases.
+fn is_control(c: u8) -> bool {
+    (c < 0x20 && c != b'\t') || c == 0x7F
+}
+
+fn unexpected_eof() -> ParseError {
+    io::Error::new(
+        ErrorKind::UnexpectedEof,
+        "Connection closed partway through the response",
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_head(input: &[u8]) -> Result<ResponseHead, String> {
+        read_response_head(&mut &input[..]).map_err(|error| error.to_string())
+    }
+
+    fn parse_chunked(input: &[u8]) -> Result<Vec<u8>, String> {
+        read_body(&mut &input[..], &BodyLength::Chunked).map_err(|error| error.to_string())
+    }
+
+    #[test]
+    fn read_valid_heads() -> Result<(), String> {
+        let head = parse_head(b"HTTP/1.1 404 Not Found\r\nContent-Type: text/html\r\n\r\n")?;
+        assert_eq!(head.version, "HTTP/1.1");
+        assert_eq!(head.status, 404);
+        assert_eq!(head.reason, "Not Found");
+        assert_eq!(head.headers.get("content-type"), Some("text/html"));
+
+        for (input, reason) in [
+            (&b"HTTP/1.1 200\r\n\r\n"[..], ""),
+            (b"HTTP/1.1 200 \r\n\r\n", ""),
+            (b"HTTP/1.0 200 OK\n\n", "OK"),
+            (b"HTTP/1.1 200 Very\tOK  indeed\r\n\r\n", "Very\tOK  indeed"),
+            (b"HTTP/1.1 200 Tr\xE8s bien\r\n\r\n", "Tr\u{FFFD}s bien"),
+        ] {
+            assert_eq!(parse_head(input)?.reason, reason);
+        }
+
+        let head = parse_head(
+            b"HTTP/1.1 200 OK\r\n\
+              Empty:\r\n\
+              Spaces:   a  b \t\r\n\
+              Set-Cookie: a=1\r\n\
+              Set-Cookie: b=2\r\n\
+              X-Weird_Name~!: \"quoted: value\"\r\n\r\n",
+        )?;
+        assert_eq!(head.headers.get("empty"), Some(""));
+        assert_eq!(head.headers.get("spaces"), Some("a  b"));
+        assert_eq!(head.headers.get_all("set-cookie"), vec!["a=1", "b=2"]);
+        assert_eq!(
+            head.headers.get("x-weird_name~!"),
+            Some("\"quoted: value\"")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_malformed_heads() {
+        for (input, error) in [
+            (&b""[..], "Connection closed partway through the response"),
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n",
+                "Connection closed partway through the response",
+            ),
+            (
+                b"HTTP/1.1 200 OK",
+                "Connection closed partway through the response",
+            ),
+            (b"\r\n", "Invalid status line ''"),
+            (b"HTTP/1.1\r\n\r\n", "Invalid status line 'HTTP/1.1'"),
+            (
+                b"http/1.1 200 OK\r\n\r\n",
+                "Invalid status line 'http/1.1 200 OK'",
+            ),
+            (
+                b"HTTP/1 200 OK\r\n\r\n",
+                "Invalid status line 'HTTP/1 200 OK'",
+            ),
+            (
+                b"HTTP/1.1.1 200 OK\r\n\r\n",
+                "Invalid status line 'HTTP/1.1.1 200 OK'",
+            ),
+            (
+                b"HTTP/x.y 200 OK\r\n\r\n",
+                "Invalid status line 'HTTP/x.y 200 OK'",
+            ),
+            (b"ICY 200 OK\r\n\r\n", "Invalid status line 'ICY 200 OK'"),
+            (
+                b"<!doctype html>\r\n\r\n",
+                "Invalid status line '<!doctype html>'",
+            ),
+            (
+                b" HTTP/1.1 200 OK\r\n\r\n",
+                "Invalid status line ' HTTP/1.1 200 OK'",
+            ),
+            (
+                b"HTTP/1.1\t200 OK\r\n\r\n",
+                "Invalid status line 'HTTP/1.1\t200 OK'",
+            ),
+            (
+                b"HTTP/1.1 200 O\x00K\r\n\r\n",
+                "Invalid status line 'HTTP/1.1 200 O\0K'",
+            ),
+            (
+                b"HTTP/1.1 200 O\rK\r\n\r\n",
+                "Invalid status line 'HTTP/1.1 200 O\rK'",
+            ),
+            (
+                b"HTTP/2.0 200 OK\r\n\r\n",
+                "Unsupported HTTP version 'HTTP/2.0'",
+            ),
+            (
+                b"HTTP/0.9 200 OK\r\n\r\n",
+                "Unsupported HTTP version 'HTTP/0.9'",
+            ),
+            (b"HTTP/1.1 OK\r\n\r\n", "Invalid status code 'OK'"),
+            (b"HTTP/1.1  200 OK\r\n\r\n", "Invalid status code ''"),
+            (b"HTTP/1.1 20 OK\r\n\r\n", "Invalid status code '20'"),
+            (b"HTTP/1.1 2000 OK\r\n\r\n", "Invalid status code '2000'"),
+            (b"HTTP/1.1 -20 OK\r\n\r\n", "Invalid status code '-20'"),
+            (b"HTTP/1.1 +20 OK\r\n\r\n", "Invalid status code '+20'"),
+            (b"HTTP/1.1 099 OK\r\n\r\n", "Invalid status code '099'"),
+            (b"HTTP/1.1 600 OK\r\n\r\n", "Invalid status code '600'"),
+            (
+                b"HTTP/1.1 2\xC3\xA9 OK\r\n\r\n",
+                "Invalid status code '2\u{E9}'",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nHost example.org\r\n\r\n",
+                "Invalid header line 'Host example.org'",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nHost : example.org\r\n\r\n",
+                "Invalid header line 'Host : example.org'",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nHost\t: example.org\r\n\r\n",
+                "Invalid header line 'Host\t: example.org'",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\n: example.org\r\n\r\n",
+                "Invalid header line ': example.org'",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nX(Y): z\r\n\r\n",
+                "Invalid header line 'X(Y): z'",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nX\x01: z\r\n\r\n",
+                "Invalid header line 'X\u{1}: z'",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nX: a\x00b\r\n\r\n",
+                "Invalid header line 'X: a\0b'",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nX: a\rContent-Length: 0\r\n\r\n",
+                "Invalid header line 'X: a\rContent-Length: 0'",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nX: a\x7Fb\r\n\r\n",
+                "Invalid header line 'X: a\u{7F}b'",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nX: a\r\n  b\r\n\r\n",
+                "Header uses obsolete line folding",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\n\tX: a\r\n\r\n",
+                "Header uses obsolete line folding",
+            ),
+        ] {
+            assert_eq!(
+                parse_head(input).map(|head| head.status),
+                Err(error.to_string()),
+                "{}",
+                input.escape_ascii()
+            );
+        }
+    }
+
+    #[test]
+    fn head_limits() {
+        let line_too_long = Err(format!("Line longer than {MAX_LINE_LENGTH} bytes"));
+
+        let mut input = b"HTTP/1.1 200 ".to_vec();
+        input.resize(MAX_LINE_LENGTH, b'A');
+        input.extend_from_slice(b"\r\n\r\n");
+        assert_eq!(parse_head(&input).map(|head| head.status), Ok(200));
+
+        let mut input = b"HTTP/1.1 200 ".to_vec();
+        input.resize(MAX_LINE_LENGTH + 1, b'A');
+        input.extend_from_slice(b"\r\n\r\n");
+        assert_eq!(parse_head(&input).map(|head| head.status), line_too_long);
+
+        // A server that never ends its line is stopped without waiting for the whole thing.
+        let mut input = b"HTTP/1.1 200 OK\r\nX: ".to_vec();
+        input.resize(100 * MAX_LINE_LENGTH, b'A');
+        assert_eq!(parse_head(&input).map(|head| head.status), line_too_long);
+
+        let mut input = b"HTTP/1.1 200 OK\r\n".to_vec();
+        for i in 0..MAX_HEADERS {
+            input.extend_from_slice(format!("X-Header-{i}: {i}\r\n").as_bytes());
+        }
+        let mut too_many = input.clone();
+        input.extend_from_slice(b"\r\n");
+        assert_eq!(
+            parse_head(&input).map(|head| head.headers.get_all("x-header-99").len()),
+            Ok(1)
+        );
+
+        too_many.extend_from_slice(b"One-Too-Many: yes\r\n\r\n");
+        assert_eq!(
+            parse_head(&too_many).map(|head| head.status),
+            Err(format!("More than {MAX_HEADERS} headers"))
+        );
+    }
+
+    #[test]
+    fn read_chunked_bodies() -> Result<(), String> {
+        for input in [
+            &b"5\r\nHello\r\n0\r\n\r\n"[..],
+            b"2\r\nHe\r\n3\r\nllo\r\n0\r\n\r\n",
+            b"0005\r\nHello\r\n000\r\n\r\n",
+            b"5;name=value;flag\r\nHello\r\n0;last\r\n\r\n",
+            b"5 ;name=\"quoted value\"\r\nHello\r\n0\r\n\r\n",
+            b"5\nHello\r\n0\n\n",
+            b"5\r\nHello\r\n0\r\nExpires: never\r\nX-Checksum: abc\r\n\r\n",
+        ] {
+            assert_eq!(parse_chunked(input)?, b"Hello", "{}", input.escape_ascii());
+        }
+        assert_eq!(
+            parse_chunked(b"A\r\n0123456789\r\na\r\n0123456789\r\n0\r\n\r\n")?.len(),
+            20
+        );
+
+        // Anything after the chunked body is left for the next response.
+        let mut stream = &b"1\r\nA\r\n0\r\n\r\nHTTP/1.1 200 OK"[..];
+        read_body(&mut stream, &BodyLength::Chunked).map_err(|error| error.to_string())?;
+        assert_eq!(stream, b"HTTP/1.1 200 OK");
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_malformed_chunked_bodies() {
+        let eof = "Connection closed partway through the response";
+        for (input, error) in [
+            (&b""[..], eof),
+            (b"5\r\nHel", eof),
+            (b"5\r\nHello", eof),
+            (b"5\r\nHello\r\n", eof),
+            (b"5\r\nHello\r\n0\r\n", eof),
+            (b"5\r\nHello\r\n0\r\nExpires: never\r\n", eof),
+            (b"\r\n", "Invalid chunk size ''"),
+            (b"zz\r\n", "Invalid chunk size 'zz'"),
+            (b"-5\r\nHello\r\n", "Invalid chunk size '-5'"),
+            (b"+5\r\nHello\r\n", "Invalid chunk size '+5'"),
+            (b"0x5\r\nHello\r\n", "Invalid chunk size '0x5'"),
+            (b" 5\r\nHello\r\n", "Invalid chunk size ' 5'"),
+            (b"5 5\r\nHello\r\n", "Invalid chunk size '5 5'"),
+            (
+                b"fffffffffffffffffffff\r\n",
+                "Invalid chunk size 'fffffffffffffffffffff'",
+            ),
+            (b"5;\r\nHello\r\n", "Invalid chunk extension '5;'"),
+            (b"5;=x\r\nHello\r\n", "Invalid chunk extension '5;=x'"),
+            (b"5;a b\r\nHello\r\n", "Invalid chunk extension '5;a b'"),
+            (b"5;a=\x00\r\nHello\r\n", "Invalid chunk extension '5;a=\0'"),
+            (b"5\r\nHelloXX0\r\n\r\n", "Chunk data not followed by CRLF"),
+            (b"5\r\nHello\n0\r\n\r\n", "Chunk data not followed by CRLF"),
+            (
+                b"3\r\nHello\r\n0\r\n\r\n",
+                "Chunk data not followed by CRLF",
+            ),
+            (
+                b"5\r\nHello\r\n0\r\nBad trailer\r\n\r\n",
+                "Invalid header line 'Bad trailer'",
+            ),
+            (
+                b"5\r\nHello\r\n0\r\nX: a\r\n b\r\n\r\n",
+                "Header uses obsolete line folding",
+            ),
+        ] {
+            assert_eq!(
+                parse_chunked(input),
+                Err(error.to_string()),
+                "{}",
+                input.escape_ascii()
+            );
+        }
+
+        let mut input = b"5\r\nHello\r\n0\r\n".to_vec();
+        for i in 0..=MAX_HEADERS {
+            input.extend_from_slice(format!("X-Trailer-{i}: {i}\r\n").as_bytes());
+        }
+        input.extend_from_slice(b"\r\n");
+        assert_eq!(
+            parse_chunked(&input),
+            Err(format!("More than {MAX_HEADERS} headers"))
+        );
+
+        let mut input = b"1".to_vec();
+        input.resize(MAX_LINE_LENGTH + 10, b'0');
+        input.extend_from_slice(b"\r\n");
+        assert_eq!(
+            parse_chunked(&input),
+            Err(format!("Line longer than {MAX_LINE_LENGTH} bytes"))
+        );
+    }
+
+    #[test]
+    fn read_fixed_length_bodies() {
+        let read = |input: &[u8], length| {
+            read_body(&mut &input[..], &BodyLength::Fixed(length))
+                .map_err(|error| error.to_string())
+        };
+        assert_eq!(read(b"Hello, world!", 5), Ok(b"Hello".to_vec()));
+        assert_eq!(read(b"", 0), Ok(Vec::new()));
+        assert_eq!(
+            read(b"Hello", 6),
+            Err("Connection closed partway through the response".to_string())
+        );
+        // Doesn't try to allocate the whole length up front.
+        assert_eq!(
+            read(b"Hello", usize::MAX),
+            Err("Connection closed partway through the response".to_string())
+        );
+    }
+
+    #[test]
+    fn body_length_rules() {
+        let get = |headers: &[(&str, &str)]| {
+            let mut response_headers = Headers::new();
+            for (name, value) in headers {
+                response_headers.append(name, value);
+            }
+            body_length(Method::Get, 200, &response_headers).map_err(|error| error.to_string())
+        };
+
+        assert_eq!(get(&[("Content-Length", "5")]), Ok(BodyLength::Fixed(5)));
+        assert_eq!(
+            get(&[("Content-Length", "5"), ("Content-Length", "5")]),
+            Ok(BodyLength::Fixed(5))
+        );
+        assert_eq!(get(&[("Content-Length", "5, 5")]), Ok(BodyLength::Fixed(5)));
+        assert_eq!(
+            get(&[("Transfer-Encoding", "chunked")]),
+            Ok(BodyLength::Chunked)
+        );
+        assert_eq!(
+            get(&[("Transfer-Encoding", "gzip, Chunked")]),
+            Ok(BodyLength::Chunked)
+        );
+        assert_eq!(
+            get(&[("Transfer-Encoding", "chunked"), ("Content-Length", "5")]),
+            Ok(BodyLength::Chunked)
+        );
+        assert_eq!(
+            get(&[("Transfer-Encoding", "chunked, gzip")]),
+            Ok(BodyLength::UntilClose)
+        );
+        assert_eq!(get(&[]), Ok(BodyLength::UntilClose));
+        for content_length in ["5, 6", "-1", "+5", "", "5 bytes", "0x10"] {
+            assert_eq!(
+                get(&[("Content-Length", content_length)]),
+                Err(format!("Invalid Content-Length: {content_length}")),
+                "{content_length}"
+            );
+        }
+
+        let headers = Headers::new().add("Content-Length", "5");
+        for (method, status) in [
+            (Method::Head, 200),
+            (Method::Get, 100),
+            (Method::Get, 204),
+            (Method::Get, 304),
+        ] {
+            assert_eq!(
+                body_length(method, status, &headers).ok(),
+                Some(BodyLength::Empty),
+                "{method:?} {status}"
+            );
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use encoding_rs::Encoding;
+
+use crate::{encoding::detect_encoding, http::headers::Headers, mime::MediaType, Url};
+
+/// The result of fetching a URL with any scheme. Non-HTTP schemes produce a `200 OK` response
+/// with a `Content-Type` header, so callers can treat every response the same way.
+#[derive(Debug, Clone)]
+pub struct Response {
+    /// The URL the response came from, after following any redirects.
+    pub url: Url,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn ok(url: &Url, media_type: &MediaType, body: Vec<u8>) -> Self {
+        Self {
+            url: url.clone(),
+            status: 200,
+            reason: "OK".to_string(),
+            headers: Headers::new().add("Content-Type", &media_type.to_string()),
+            body,
+        }
+    }
+
+    pub fn not_found(url: &Url) -> Self {
+        Self {
+            url: url.clone(),
+            status: 404,
+            reason: "Not Found".to_string(),
+            headers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn content_type(&self) -> Option<MediaType> {
+        self.headers.get("content-type").and_then(MediaType::parse)
+    }
+
+    /// Whether the status is a 4xx client error or a 5xx server error.
+    pub fn is_error(&self) -> bool {
+        (400..=599).contains(&self.status)
+    }
+
+    /// The character encoding of the body, from its byte order mark, `Content-Type` header or
+    /// `<meta charset>` declaration.
+    pub fn encoding(&self) -> &'static Encoding {
+        detect_encoding(self.content_type().as_ref(), &self.body)
+    }
+
+    /// The body decoded as text using [`Response::encoding`]. Malformed sequences are replaced
+    /// with U+FFFD rather than failing.
+    pub fn text(&self) -> String {
+        let (text, _) = self.encoding().decode_with_bom_removal(&self.body);
+        text.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ok_response_has_content_type() {
+        let url = Url::parse("about:blank").unwrap();
+        let response = Response::ok(
+            &url,
+            &MediaType::new("text/html").with_parameter("charset", "utf-8"),
+            b"<p>Hi</p>".to_vec(),
+        );
+        assert_eq!(response.status, 200);
+        assert!(!response.is_error());
+        assert_eq!(
+            response.headers.get("Content-Type"),
+            Some("text/html;charset=utf-8")
+        );
+        assert_eq!(response.content_type().unwrap().essence, "text/html");
+    }
+
+    #[test]
+    fn text_replaces_invalid_utf8() {
+        let url = Url::parse("about:blank").unwrap();
+        let response = Response::ok(
+            &url,
+            &MediaType::new("text/plain").with_parameter("charset", "utf-8"),
+            b"caf\xE9".to_vec(),
+        );
+        assert_eq!(response.text(), "caf\u{FFFD}");
+    }
+
+    #[test]
+    fn text_uses_detected_encoding() {
+        let url = Url::parse("about:blank").unwrap();
+        let response = Response::ok(
+            &url,
+            &MediaType::new("text/html"),
+            b"<meta charset=iso-8859-1><p>Caf\xE9 \x93quoted\x94</p>".to_vec(),
+        );
+        assert_eq!(response.encoding(), encoding_rs::WINDOWS_1252);
+        assert_eq!(
+            response.text(),
+            "<meta charset=iso-8859-1><p>Caf\u{e9} \u{201c}quoted\u{201d}</p>"
+        );
+    }
+
+    #[test]
+    fn text_strips_bom() {
+        let url = Url::parse("about:blank").unwrap();
+        let response = Response::ok(
+            &url,
+            &MediaType::new("text/html"),
+            b"\xEF\xBB\xBFHi".to_vec(),
+        );
+        assert_eq!(response.text(), "Hi");
+    }
+
+    #[test]
+    fn error_statuses() {
+        let url = Url::parse("about:blank").unwrap();
+        let mut response = Response::not_found(&url);
+        assert!(response.is_error());
+        for (status, is_error) in [(200, false), (304, false), (399, false), (500, true)] {
+            response.status = status;
+            assert_eq!(response.is_error(), is_error, "{status}");
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Fixtures shared by tests across the crate.
+
+use crate::{
+    cache::RequestCache,
+    scheme::{RequestContext, SchemeRegistry},
+    url::{HttpUrl, Scheme},
+    Url,
+};
+
+use super::{ConnectionPool, CookieJar, Request};
+
+pub fn http_url(url: &str) -> HttpUrl {
+    let Scheme::Http(http_url) = Url::parse(url).unwrap().scheme else {
+        unreachable!();
+    };
+    http_url
+}
+
+pub fn request(url: &str) -> Request {
+    Request::get(&http_url(url))
+}
+
+/// A context with the built-in schemes and no history.
+pub fn request_context<'a>(
+    connection_pool: &'a mut ConnectionPool,
+    cache: &'a mut RequestCache,
+    cookie_jar: &'a mut CookieJar,
+) -> RequestContext<'a> {
+    RequestContext {
+        connection_pool,
+        cache,
+        cookie_jar,
+        schemes: SchemeRegistry::builtin(),
+        history: &[],
+    }
+}
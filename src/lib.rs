@@ -1,12 +1,19 @@
 mod browser;
 mod cache;
 pub mod config;
+mod encoding;
 mod html;
 mod http;
+mod mime;
+mod scheme;
 mod url;
 
 pub use browser::Browser;
 pub use cache::RequestCache;
 pub use html::lex;
-pub use http::{request, ConnectionPool};
-pub use url::Url;
+pub use http::{
+    request, send, ConnectionPool, Cookie, CookieJar, Headers, Method, NetworkError,
+    NetworkErrorKind, Request, Response, SameSite,
+};
+pub use scheme::{RequestContext, SchemeHandler, SchemeRegistry};
+pub use url::{CustomUrl, HttpUrl, Origin, Scheme, Url, UrlError};
@@ -3,8 +3,8 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilte
 
 use bowsernet::{
     config::{
-        DEFAULT_HEIGHT, DEFAULT_URL, DEFAULT_WIDTH, FPS_FONT_SIZE, FPS_HEIGHT, FPS_HPADDING,
-        FPS_VPADDING, FPS_WIDTH,
+        CLEAR_CACHE_FLAG, DEFAULT_HEIGHT, DEFAULT_URL, DEFAULT_WIDTH, FPS_FONT_SIZE, FPS_HEIGHT,
+        FPS_HPADDING, FPS_VPADDING, FPS_WIDTH,
     },
     Browser, Url,
 };
@@ -24,15 +24,22 @@ async fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
     setup_tracing()?;
 
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    let clear_cache = args.iter().any(|arg| arg == CLEAR_CACHE_FLAG);
+    args.retain(|arg| arg != CLEAR_CACHE_FLAG);
 
     let mut browser = Browser::new()?;
+    if clear_cache {
+        browser.clear_cache()?;
+    }
 
-    let url = Url::parse(args.get(1).unwrap_or(&DEFAULT_URL.to_string())).unwrap_or_else(|error| {
-        tracing::error!("Invalid URL: {}", error);
-        Url::parse("about:blank").unwrap()
-    });
-    browser.load(&url)?;
+    let url = browser
+        .parse_url(args.get(1).unwrap_or(&DEFAULT_URL.to_string()))
+        .unwrap_or_else(|error| {
+            tracing::error!("Invalid URL: {}", error);
+            Url::parse("about:blank").unwrap()
+        });
+    browser.load(&url);
 
     let mut frame: u64 = 0;
     let mut fps = format!("FPS: {}", get_fps());
@@ -42,7 +49,7 @@ async fn main() -> color_eyre::Result<()> {
         browser.handle_input();
         browser.draw();
 
-        if frame % 20 == 0 {
+        if frame.is_multiple_of(20) {
             fps = format!("FPS: {}", get_fps());
         }
         draw_rectangle(
@@ -0,0 +1,189 @@
+use std::fmt::Display;
+
+/// A parsed MIME type such as `text/html;charset=utf-8`, as found in `Content-Type` headers
+/// and `data:` URLs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaType {
+    /// The lowercased `type/subtype`.
+    pub essence: String,
+    /// Parameter names are lowercased, values keep their original case.
+    pub parameters: Vec<(String, String)>,
+}
+
+impl MediaType {
+    pub fn new(essence: &str) -> Self {
+        Self {
+            essence: essence.to_ascii_lowercase(),
+            parameters: Vec::new(),
+        }
+    }
+
+    pub fn with_parameter(mut self, name: &str, value: &str) -> Self {
+        self.parameters
+            .push((name.to_ascii_lowercase(), value.to_string()));
+        self
+    }
+
+    /// Parses a MIME type following the WHATWG MIME Sniffing standard. Returns `None` if the
+    /// type or subtype is missing or invalid.
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim_ascii();
+        let (essence, parameters) = input.split_once(';').unwrap_or((input, ""));
+        let (type_, subtype) = essence.split_once('/')?;
+        let subtype = subtype.trim_ascii_end();
+        if !is_token(type_) || !is_token(subtype) {
+            return None;
+        }
+
+        Some(Self {
+            essence: format!("{type_}/{subtype}").to_ascii_lowercase(),
+            parameters: parse_parameters(parameters),
+        })
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(parameter_name, _)| parameter_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Display for MediaType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.essence)?;
+        for (name, value) in &self.parameters {
+            if is_token(value) {
+                write!(f, ";{}={}", name, value)?;
+            } else {
+                let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, ";{}=\"{}\"", name, escaped)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_parameters(mut rest: &str) -> Vec<(String, String)> {
+    let mut parameters: Vec<(String, String)> = Vec::new();
+    while !rest.is_empty() {
+        rest = rest.trim_ascii_start();
+        let name_end = rest.find([';', '=']).unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = &rest[name_end..];
+
+        if let Some(after_semicolon) = rest.strip_prefix(';') {
+            // A parameter without a value is ignored.
+            rest = after_semicolon;
+            continue;
+        }
+        let Some(after_equals) = rest.strip_prefix('=') else {
+            break;
+        };
+
+        let value = if let Some(after_quote) = after_equals.strip_prefix('"') {
+            let (value, after_value) = parse_quoted_string(after_quote);
+            rest = after_value.split_once(';').map_or("", |(_, rest)| rest);
+            value
+        } else {
+            let (value, after_value) = after_equals.split_once(';').unwrap_or((after_equals, ""));
+            rest = after_value;
+            let value = value.trim_ascii_end();
+            if value.is_empty() {
+                continue;
+            }
+            value.to_string()
+        };
+
+        if is_token(&name) && !parameters.iter().any(|(existing, _)| *existing == name) {
+            parameters.push((name, value));
+        }
+    }
+    parameters
+}
+
+/// Parses the rest of a quoted string after its opening quote, returning the unescaped value
+/// and whatever follows the closing quote.
+fn parse_quoted_string(input: &str) -> (String, &str) {
+    let mut value = String::new();
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return (value, &input[i + 1..]),
+            '\\' => match chars.next() {
+                Some((_, escaped)) => value.push(escaped),
+                None => value.push('\\'),
+            },
+            c => value.push(c),
+        }
+    }
+    (value, "")
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_essence_only() {
+        let media_type = MediaType::parse("Text/HTML").unwrap();
+        assert_eq!(media_type.essence, "text/html");
+        assert!(media_type.parameters.is_empty());
+    }
+
+    #[test]
+    fn parse_with_parameters() {
+        let media_type = MediaType::parse("text/html; Charset=UTF-8 ;foo=bar").unwrap();
+        assert_eq!(media_type.essence, "text/html");
+        assert_eq!(
+            media_type.parameters,
+            vec![
+                ("charset".to_string(), "UTF-8".to_string()),
+                ("foo".to_string(), "bar".to_string())
+            ]
+        );
+        assert_eq!(media_type.parameter("CHARSET"), Some("UTF-8"));
+        assert_eq!(media_type.parameter("missing"), None);
+    }
+
+    #[test]
+    fn parse_quoted_parameter() {
+        let media_type = MediaType::parse(r#"text/plain;name="a \"b\"; c";x=y"#).unwrap();
+        assert_eq!(media_type.parameter("name"), Some(r#"a "b"; c"#));
+        assert_eq!(media_type.parameter("x"), Some("y"));
+    }
+
+    #[test]
+    fn parse_ignores_invalid_and_duplicate_parameters() {
+        let media_type = MediaType::parse("text/plain;novalue;=x;empty=;a=1;a=2").unwrap();
+        assert_eq!(
+            media_type.parameters,
+            vec![("a".to_string(), "1".to_string())]
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(MediaType::parse(""), None);
+        assert_eq!(MediaType::parse("text"), None);
+        assert_eq!(MediaType::parse("text/"), None);
+        assert_eq!(MediaType::parse("te xt/plain"), None);
+    }
+
+    #[test]
+    fn display_quotes_non_token_values() {
+        let media_type = MediaType::new("text/plain")
+            .with_parameter("charset", "utf-8")
+            .with_parameter("title", "Hello \"world\"");
+        assert_eq!(
+            media_type.to_string(),
+            r#"text/plain;charset=utf-8;title="Hello \"world\"""#
+        );
+    }
+}
@@ -0,0 +1,292 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use crate::{
+    cache::RequestCache,
+    http::{
+        handle_builtin_request, handle_data_request, handle_file_request, send, ConnectionPool,
+        CookieJar, Request, Response,
+    },
+    url::{parse_builtin, parse_data, parse_file, parse_http, CustomUrl, Scheme},
+    Url, UrlError,
+};
+
+/// Everything a scheme handler might need from the browser to fetch a URL.
+pub struct RequestContext<'a> {
+    pub connection_pool: &'a mut ConnectionPool,
+    pub cache: &'a mut RequestCache,
+    pub cookie_jar: &'a mut CookieJar,
+    /// The handlers for every scheme, e.g. for following a redirect to a custom scheme.
+    pub schemes: &'a SchemeRegistry,
+    /// Previously loaded URLs, oldest first.
+    pub history: &'a [Url],
+}
+
+/// Parses and fetches URLs for one scheme, such as `https` or a custom `docs` scheme.
+pub trait SchemeHandler: Send + Sync {
+    /// Parses everything after the `scheme:` prefix. By default the URL is kept as it is in a
+    /// [`CustomUrl`].
+    fn parse(&self, scheme: &str, rest: &str) -> Result<Scheme, UrlError> {
+        Ok(Scheme::Custom(CustomUrl {
+            scheme: scheme.to_string(),
+            path: rest.to_string(),
+        }))
+    }
+
+    /// Fetches the document at the URL, which was parsed by [`SchemeHandler::parse`].
+    fn request(&self, url: &Url, context: &mut RequestContext) -> color_eyre::Result<Response>;
+}
+
+/// Maps scheme names to the handlers that parse and fetch URLs with that scheme. Starts out
+/// with handlers for `http`, `https`, `file`, `data` and `about`.
+pub struct SchemeRegistry {
+    handlers: HashMap<String, Box<dyn SchemeHandler>>,
+}
+
+impl SchemeRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            handlers: HashMap::new(),
+        };
+        registry.register("http", HttpHandler { tls: false });
+        registry.register("https", HttpHandler { tls: true });
+        registry.register("file", FileHandler);
+        registry.register("data", DataHandler);
+        registry.register("about", BuiltinHandler);
+        registry
+    }
+
+    /// A shared registry with only the built-in schemes.
+    pub fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<SchemeRegistry> = OnceLock::new();
+        BUILTIN.get_or_init(SchemeRegistry::new)
+    }
+
+    /// Registers a handler for a scheme, replacing any existing handler for it.
+    pub fn register(&mut self, scheme: &str, handler: impl SchemeHandler + 'static) {
+        self.handlers
+            .insert(scheme.to_ascii_lowercase(), Box::new(handler));
+    }
+
+    pub fn get(&self, scheme: &str) -> Option<&dyn SchemeHandler> {
+        self.handlers
+            .get(&scheme.to_ascii_lowercase())
+            .map(|handler| handler.as_ref())
+    }
+}
+
+impl Default for SchemeRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn unexpected_url(url: &Url) -> color_eyre::Report {
+    color_eyre::eyre::eyre!("Scheme handler can't handle URL '{url}'")
+}
+
+struct HttpHandler {
+    tls: bool,
+}
+
+impl SchemeHandler for HttpHandler {
+    fn parse(&self, _scheme: &str, rest: &str) -> Result<Scheme, UrlError> {
+        Ok(Scheme::Http(parse_http(rest, self.tls)?))
+    }
+
+    fn request(&self, url: &Url, context: &mut RequestContext) -> color_eyre::Result<Response> {
+        let Scheme::Http(http_url) = &url.scheme else {
+            return Err(unexpected_url(url));
+        };
+        send(&Request::get(http_url), context)
+    }
+}
+
+struct FileHandler;
+
+impl SchemeHandler for FileHandler {
+    fn parse(&self, _scheme: &str, rest: &str) -> Result<Scheme, UrlError> {
+        Ok(Scheme::File(parse_file(rest)))
+    }
+
+    fn request(&self, url: &Url, _context: &mut RequestContext) -> color_eyre::Result<Response> {
+        let Scheme::File(file_url) = &url.scheme else {
+            return Err(unexpected_url(url));
+        };
+        handle_file_request(url, file_url)
+    }
+}
+
+struct DataHandler;
+
+impl SchemeHandler for DataHandler {
+    fn parse(&self, _scheme: &str, rest: &str) -> Result<Scheme, UrlError> {
+        Ok(Scheme::Data(parse_data(rest)?))
+    }
+
+    fn request(&self, url: &Url, _context: &mut RequestContext) -> color_eyre::Result<Response> {
+        let Scheme::Data(data_url) = &url.scheme else {
+            return Err(unexpected_url(url));
+        };
+        tracing::info!("Data URL has media type {}", data_url.media_type);
+        handle_data_request(url, data_url)
+    }
+}
+
+struct BuiltinHandler;
+
+impl SchemeHandler for BuiltinHandler {
+    fn parse(&self, _scheme: &str, rest: &str) -> Result<Scheme, UrlError> {
+        Ok(Scheme::Builtin(parse_builtin(rest)?))
+    }
+
+    fn request(&self, url: &Url, context: &mut RequestContext) -> color_eyre::Result<Response> {
+        let Scheme::Builtin(builtin_url) = &url.scheme else {
+            return Err(unexpected_url(url));
+        };
+        handle_builtin_request(url, builtin_url, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::testing::request_context, mime::MediaType, request};
+
+    /// Serves pages from an in-memory archive, like a `docs:` scheme might.
+    struct DocsHandler {
+        pages: HashMap<&'static str, &'static str>,
+    }
+
+    impl SchemeHandler for DocsHandler {
+        fn request(
+            &self,
+            url: &Url,
+            _context: &mut RequestContext,
+        ) -> color_eyre::Result<Response> {
+            let Scheme::Custom(custom_url) = &url.scheme else {
+                return Err(unexpected_url(url));
+            };
+            let page = custom_url.path.trim_start_matches('/');
+            let content = self
+                .pages
+                .get(page)
+                .ok_or_else(|| color_eyre::eyre::eyre!("No such page: {page}"))?;
+            Ok(Response::ok(
+                url,
+                &MediaType::new("text/html"),
+                content.as_bytes().to_vec(),
+            ))
+        }
+    }
+
+    fn docs_registry() -> SchemeRegistry {
+        let mut registry = SchemeRegistry::new();
+        registry.register(
+            "docs",
+            DocsHandler {
+                pages: HashMap::from([("guide/intro.html", "<p>Welcome!</p>")]),
+            },
+        );
+        registry
+    }
+
+    fn request_with(url: &Url, schemes: &SchemeRegistry) -> color_eyre::Result<Response> {
+        request(
+            url,
+            &mut RequestContext {
+                schemes,
+                ..request_context(
+                    &mut ConnectionPool::new(),
+                    &mut RequestCache::new(),
+                    &mut CookieJar::new(),
+                )
+            },
+        )
+    }
+
+    #[test]
+    fn parse_custom_scheme() -> color_eyre::Result<()> {
+        let url = Url::parse_with("DOCS://guide/intro.html", &docs_registry())?;
+        assert_eq!(
+            url.scheme,
+            Scheme::Custom(CustomUrl {
+                scheme: "docs".to_string(),
+                path: "//guide/intro.html".to_string(),
+            })
+        );
+        assert_eq!(url.to_string(), "docs://guide/intro.html");
+        Ok(())
+    }
+
+    #[test]
+    fn unregistered_scheme_is_unsupported() {
+        assert_eq!(
+            Url::parse("docs://guide/intro.html"),
+            Err(UrlError::UnsupportedScheme("docs".to_string()))
+        );
+    }
+
+    #[test]
+    fn request_custom_scheme() -> color_eyre::Result<()> {
+        let schemes = docs_registry();
+        let url = Url::parse_with("docs://guide/intro.html", &schemes)?;
+        assert_eq!(request_with(&url, &schemes)?.text(), "<p>Welcome!</p>");
+
+        let url = Url::parse_with("view-source:docs://guide/intro.html", &schemes)?;
+        let response = request_with(&url, &schemes)?;
+        assert!(response.url.view_source);
+        assert_eq!(response.text(), "<p>Welcome!</p>");
+
+        let url = Url::parse_with("docs://missing.html", &schemes)?;
+        assert!(request_with(&url, &schemes).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_to_custom_scheme() -> color_eyre::Result<()> {
+        let base = Url::parse("https://example.org/index.html")?;
+        assert_eq!(
+            base.resolve("docs://guide/intro.html"),
+            Err(UrlError::UnsupportedScheme("docs".to_string()))
+        );
+        assert_eq!(
+            base.resolve_with("DOCS://guide/intro.html", &docs_registry())?
+                .to_string(),
+            "docs://guide/intro.html"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn builtin_schemes_can_be_replaced() -> color_eyre::Result<()> {
+        let mut schemes = SchemeRegistry::new();
+        schemes.register(
+            "Data",
+            DocsHandler {
+                pages: HashMap::from([("hello", "Hello from a custom handler")]),
+            },
+        );
+        let url = Url::parse_with("data:hello", &schemes)?;
+        assert_eq!(
+            request_with(&url, &schemes)?.text(),
+            "Hello from a custom handler"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn request_builtin_schemes() -> color_eyre::Result<()> {
+        let schemes = SchemeRegistry::new();
+        for (input, expected) in [("about:blank", ""), ("data:,Hello%20world", "Hello world")] {
+            assert_eq!(
+                request_with(&Url::parse(input)?, &schemes)?.text(),
+                expected
+            );
+        }
+        assert_eq!(
+            Url::parse("about:nothing"),
+            Err(UrlError::UnknownAboutPage("nothing".to_string()))
+        );
+        Ok(())
+    }
+}
@@ -1,105 +1,237 @@
-use std::fmt::Display;
+use std::{
+    fmt::Display,
+    net::{Ipv4Addr, Ipv6Addr},
+    path::PathBuf,
+};
 
-use color_eyre::eyre::OptionExt;
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
 
-#[derive(Debug, PartialEq)]
+use crate::{mime::MediaType, scheme::SchemeRegistry};
+use percent_encoding::{percent_decode, percent_encode, EncodeSet};
+
+pub use error::UrlError;
+pub use origin::Origin;
+
+mod error;
+mod origin;
+mod percent_encoding;
+pub(crate) mod public_suffix;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Url {
     pub scheme: Scheme,
     pub view_source: bool,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Scheme {
     Http(HttpUrl),
     File(FileUrl),
     Data(DataUrl),
     Builtin(BuiltinUrl),
+    /// A URL with a scheme provided by a custom [`SchemeHandler`](crate::SchemeHandler).
+    Custom(CustomUrl),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct HttpUrl {
     pub tls: bool,
+    pub username: String,
+    pub password: Option<String>,
+    /// The ASCII (punycode) form of the host, used for connecting, TLS and caching. IPv6
+    /// addresses are stored with their surrounding brackets, e.g. `[::1]`.
     pub host: String,
     pub port: u16,
     pub path: String,
+    pub query: Option<String>,
+    pub fragment: Option<String>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FileUrl {
     pub path: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DataUrl {
-    pub content_type: String,
-    pub contents: String,
+    pub media_type: MediaType,
+    /// Whether the URL was written with `;base64`. Only affects how it is displayed.
+    pub base64: bool,
+    pub data: Vec<u8>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BuiltinUrl {
-    AboutBlank,
+    Blank,
+    Version,
+    Cache,
+    Connections,
+    History,
+    Config,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomUrl {
+    /// The lowercased scheme name.
+    pub scheme: String,
+    /// Everything after the `scheme:` prefix, e.g. `//guide/intro.html` for
+    /// `docs://guide/intro.html`.
+    pub path: String,
 }
 
 impl Url {
-    pub fn parse(url: &str) -> color_eyre::Result<Self> {
-        if let Some(builtin_url) = parse_builtin(url) {
-            return Ok(Url {
-                scheme: Scheme::Builtin(builtin_url),
-                view_source: false,
-            });
-        }
-
-        let view_source = url.starts_with("view-source:");
-        let url = url.strip_prefix("view-source:").unwrap_or(url);
-
-        if let Some(url) = url.strip_prefix("data:") {
-            let (content_type, contents) = url
-                .split_once(',')
-                .ok_or_eyre("Data URLs must have a content type")?;
-            return Ok(Url {
-                scheme: Scheme::Data(DataUrl {
-                    content_type: content_type.to_string(),
-                    contents: contents.to_string(),
-                }),
-                view_source,
-            });
-        }
-
-        let (scheme, url) = url.split_once("://").ok_or_eyre("URL must have a scheme")?;
-
-        if scheme == "file" {
-            return Ok(Url {
-                scheme: Scheme::File(FileUrl {
-                    path: url.to_string(),
-                }),
-                view_source,
-            });
-        }
-
-        let (mut host, url) = url.split_once('/').unwrap_or((url, ""));
-        let path = format!("/{url}");
-
-        let port = if let Some((actual_host, port)) = host.split_once(':') {
-            host = actual_host;
-            port.parse()?
-        } else {
-            match scheme {
-                "http" => 80,
-                "https" => 443,
-                _ => return Err(color_eyre::eyre::eyre!("Scheme must be 'http' or 'https'.")),
-            }
+    /// Parses a URL with one of the built-in schemes. Use [`Url::parse_with`] (or
+    /// [`Browser::parse_url`](crate::Browser::parse_url)) to also allow custom schemes.
+    pub fn parse(url: &str) -> Result<Self, UrlError> {
+        Url::parse_with(url, SchemeRegistry::builtin())
+    }
+
+    /// Parses a URL, handing everything after the scheme to the handler registered for it.
+    pub fn parse_with(url: &str, schemes: &SchemeRegistry) -> Result<Self, UrlError> {
+        let url = normalize_input(url);
+        let url = url.as_str();
+
+        let (view_source, url) = match strip_prefix_ignore_case(url, "view-source:") {
+            Some(url) => (true, url),
+            None => (false, url),
         };
 
+        let (scheme, rest) = url
+            .split_once(':')
+            .filter(|_| has_scheme(url))
+            .ok_or(UrlError::MissingScheme)?;
+
+        let scheme_name = scheme.to_ascii_lowercase();
+        let scheme = schemes
+            .get(&scheme_name)
+            .ok_or_else(|| UrlError::UnsupportedScheme(scheme_name.clone()))?
+            .parse(&scheme_name, rest)?;
+
         Ok(Url {
-            scheme: Scheme::Http(HttpUrl {
-                tls: scheme == "https",
-                host: host.to_string(),
-                port,
-                path,
-            }),
+            scheme,
             view_source,
         })
     }
+
+    /// Resolves a URL reference (e.g. from a `Location` header or a link) against this URL,
+    /// following RFC 3986 section 5. Absolute references can only use the built-in schemes;
+    /// use [`Url::resolve_with`] (or [`Browser::resolve_url`](crate::Browser::resolve_url)) to
+    /// also allow custom schemes.
+    pub fn resolve(&self, reference: &str) -> Result<Self, UrlError> {
+        self.resolve_with(reference, SchemeRegistry::builtin())
+    }
+
+    /// Resolves a URL reference against this URL, parsing absolute references with the
+    /// handler registered for their scheme.
+    pub fn resolve_with(
+        &self,
+        reference: &str,
+        schemes: &SchemeRegistry,
+    ) -> Result<Self, UrlError> {
+        let reference = normalize_input(reference);
+        let mut reference = reference.as_str();
+
+        if has_scheme(reference) {
+            let (scheme, rest) = reference.split_once(':').unwrap_or_default();
+            // `http:page.html` is relative to an `http` base URL.
+            let relative = match &self.scheme {
+                Scheme::Http(http_url) => scheme.eq_ignore_ascii_case(http_url.scheme_name()),
+                _ => false,
+            };
+            if !relative {
+                return Url::parse_with(reference, schemes);
+            }
+            reference = rest;
+        }
+
+        let scheme = match &self.scheme {
+            Scheme::Http(http_url) => {
+                let (reference_path, reference_rest) =
+                    reference.split_at(reference.find(['?', '#']).unwrap_or(reference.len()));
+                let reference = format!("{}{}", reference_path.replace('\\', "/"), reference_rest);
+                if reference.starts_with("//") {
+                    return Url::parse(&format!("{}:{}", http_url.scheme_name(), reference));
+                }
+                let (path, query, fragment) =
+                    resolve_path(&http_url.path, http_url.query.as_deref(), &reference);
+                Scheme::Http(HttpUrl {
+                    path: percent_encode(path, EncodeSet::Path),
+                    query: query.map(|query| percent_encode(query, EncodeSet::Query)),
+                    fragment: fragment
+                        .map(|fragment| percent_encode(fragment, EncodeSet::Fragment)),
+                    ..http_url.clone()
+                })
+            }
+            Scheme::File(file_url) => {
+                if reference.starts_with("//") {
+                    return Url::parse(&format!("file:{reference}"));
+                }
+                let (base_path, base_query, _) = split_path(&file_url.path);
+                let (mut path, query, fragment) = resolve_path(base_path, base_query, reference);
+                if let Some(query) = query {
+                    path.push('?');
+                    path.push_str(&query);
+                }
+                if let Some(fragment) = fragment {
+                    path.push('#');
+                    path.push_str(&fragment);
+                }
+                Scheme::File(FileUrl { path })
+            }
+            Scheme::Data(_) | Scheme::Builtin(_) | Scheme::Custom(_) => {
+                return Err(UrlError::CannotResolveRelative(reference.to_string()))
+            }
+        };
+
+        Ok(Url {
+            scheme,
+            view_source: false,
+        })
+    }
+
+    /// The origin of the URL. `file:`, `data:` and `about:` URLs get a new opaque origin each
+    /// time this is called. A `view-source:` URL has the origin of the URL it wraps.
+    pub fn origin(&self) -> Origin {
+        match &self.scheme {
+            Scheme::Http(http_url) => Origin::Tuple {
+                scheme: http_url.scheme_name(),
+                host: http_url.host.clone(),
+                port: http_url.port,
+            },
+            Scheme::File(_) | Scheme::Data(_) | Scheme::Builtin(_) | Scheme::Custom(_) => {
+                Origin::new_opaque()
+            }
+        }
+    }
+
+    /// The URL without any username or password, for showing or storing it somewhere a
+    /// password shouldn't end up.
+    pub fn without_userinfo(&self) -> Url {
+        match &self.scheme {
+            Scheme::Http(http_url) => Url {
+                scheme: Scheme::Http(http_url.without_userinfo()),
+                view_source: self.view_source,
+            },
+            _ => self.clone(),
+        }
+    }
+}
+
+impl Scheme {
+    /// The lowercased scheme name, used to look up the scheme's handler.
+    pub fn name(&self) -> &str {
+        match self {
+            Scheme::Http(http_url) => http_url.scheme_name(),
+            Scheme::File(_) => "file",
+            Scheme::Data(_) => "data",
+            Scheme::Builtin(_) => "about",
+            Scheme::Custom(custom_url) => &custom_url.scheme,
+        }
+    }
 }
 
 impl Display for Url {
@@ -112,20 +244,123 @@ impl Display for Url {
             Scheme::File(file_url) => write!(f, "{}", file_url),
             Scheme::Data(data_url) => write!(f, "{}", data_url),
             Scheme::Builtin(builtin_url) => write!(f, "{}", builtin_url),
+            Scheme::Custom(custom_url) => write!(f, "{}", custom_url),
+        }
+    }
+}
+
+impl HttpUrl {
+    pub fn scheme_name(&self) -> &'static str {
+        if self.tls {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
+    pub fn default_port(&self) -> u16 {
+        if self.tls {
+            443
+        } else {
+            80
+        }
+    }
+
+    /// The host, followed by the port unless it is the default for the scheme. This is what
+    /// goes in the `Host` header.
+    pub fn authority(&self) -> String {
+        if self.port == self.default_port() {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    /// The host converted back to Unicode for display, e.g. `bücher.example` rather than
+    /// `xn--bcher-kva.example`.
+    pub fn unicode_host(&self) -> String {
+        let (host, result) = idna::domain_to_unicode(&self.host);
+        if result.is_ok() {
+            host
+        } else {
+            self.host.clone()
+        }
+    }
+
+    /// The host without IPv6 brackets, as needed for socket addresses and TLS server names.
+    pub fn connect_host(&self) -> &str {
+        self.host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(&self.host)
+    }
+
+    pub fn without_userinfo(&self) -> HttpUrl {
+        HttpUrl {
+            username: String::new(),
+            password: None,
+            ..self.clone()
+        }
+    }
+
+    /// The value of a basic `Authorization` header for the username and password, if the URL
+    /// has either (RFC 7617).
+    pub fn basic_authorization(&self) -> Option<String> {
+        if self.username.is_empty() && self.password.is_none() {
+            return None;
+        }
+        let mut credentials = percent_decode(&self.username);
+        credentials.push(b':');
+        credentials.extend(percent_decode(self.password.as_deref().unwrap_or_default()));
+        Some(format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(credentials)
+        ))
+    }
+
+    /// The percent-encoded path and query as sent in the HTTP request line. The fragment is
+    /// never sent to the server.
+    pub fn request_target(&self) -> String {
+        let path = percent_encode(&self.path, EncodeSet::Path);
+        match &self.query {
+            Some(query) => format!("{}?{}", path, percent_encode(query, EncodeSet::Query)),
+            None => path,
+        }
+    }
+}
+
+impl FileUrl {
+    /// The decoded filesystem path, without any query or fragment.
+    pub fn to_file_path(&self) -> PathBuf {
+        let (path, _, _) = split_path(&self.path);
+        let bytes = percent_decode(path);
+        #[cfg(unix)]
+        {
+            use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+            PathBuf::from(OsString::from_vec(bytes))
+        }
+        #[cfg(not(unix))]
+        {
+            PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
         }
     }
 }
 
 impl Display for HttpUrl {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "http{}://{}:{}{}",
-            if self.tls { "s" } else { "" },
-            self.host,
-            self.port,
-            self.path
-        )
+        write!(f, "{}://", self.scheme_name())?;
+        if !self.username.is_empty() || self.password.is_some() {
+            write!(f, "{}", percent_encode(&self.username, EncodeSet::Userinfo))?;
+            if let Some(password) = &self.password {
+                write!(f, ":{}", percent_encode(password, EncodeSet::Userinfo))?;
+            }
+            write!(f, "@")?;
+        }
+        write!(f, "{}{}", self.authority(), self.request_target())?;
+        if let Some(fragment) = &self.fragment {
+            write!(f, "#{}", percent_encode(fragment, EncodeSet::Fragment))?;
+        }
+        Ok(())
     }
 }
 
@@ -137,22 +372,338 @@ impl Display for FileUrl {
 
 impl Display for DataUrl {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "data:{},{}", self.content_type, self.contents)
+        write!(f, "data:{}", self.media_type)?;
+        if self.base64 {
+            write!(f, ";base64,{}", BASE64.encode(&self.data))
+        } else {
+            write!(f, ",{}", percent_encode(&self.data, EncodeSet::Opaque))
+        }
     }
 }
 
 impl Display for BuiltinUrl {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            BuiltinUrl::AboutBlank => write!(f, "about:blank"),
+            BuiltinUrl::Blank => write!(f, "about:blank"),
+            BuiltinUrl::Version => write!(f, "about:version"),
+            BuiltinUrl::Cache => write!(f, "about:cache"),
+            BuiltinUrl::Connections => write!(f, "about:connections"),
+            BuiltinUrl::History => write!(f, "about:history"),
+            BuiltinUrl::Config => write!(f, "about:config"),
         }
     }
 }
 
-fn parse_builtin(url: &str) -> Option<BuiltinUrl> {
+impl Display for CustomUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.scheme, self.path)
+    }
+}
+
+/// Parses the part of an `about:` URL after the scheme.
+pub(crate) fn parse_builtin(url: &str) -> Result<BuiltinUrl, UrlError> {
     match url.to_lowercase().as_str() {
-        "about:blank" => Some(BuiltinUrl::AboutBlank),
-        _ => None,
+        "blank" => Ok(BuiltinUrl::Blank),
+        "version" => Ok(BuiltinUrl::Version),
+        "cache" => Ok(BuiltinUrl::Cache),
+        "connections" => Ok(BuiltinUrl::Connections),
+        "history" => Ok(BuiltinUrl::History),
+        "config" => Ok(BuiltinUrl::Config),
+        _ => Err(UrlError::UnknownAboutPage(url.to_string())),
+    }
+}
+
+/// Parses the part of a `file:` URL after the scheme.
+pub(crate) fn parse_file(url: &str) -> FileUrl {
+    FileUrl {
+        path: url.strip_prefix("//").unwrap_or(url).to_string(),
+    }
+}
+
+/// Strips leading and trailing C0 control characters and spaces, and removes all tabs and
+/// newlines, as browsers do with typed or copied URLs.
+fn normalize_input(url: &str) -> String {
+    url.trim_matches(|c: char| c <= ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect()
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    s.get(..prefix.len())
+        .filter(|start| start.eq_ignore_ascii_case(prefix))
+        .map(|_| &s[prefix.len()..])
+}
+
+/// Parses the part of an `http:` or `https:` URL after the scheme into its canonical form.
+pub(crate) fn parse_http(url: &str, tls: bool) -> Result<HttpUrl, UrlError> {
+    let url = url.trim_start_matches(['/', '\\']);
+    let (authority, url) = match url.find(['/', '\\', '?', '#']) {
+        Some(index) => url.split_at(index),
+        None => (url, ""),
+    };
+    let (path, query, fragment) = split_path(url);
+    let path = remove_dot_segments(&path.replace('\\', "/"));
+    let path = if path.is_empty() {
+        "/".to_string()
+    } else {
+        path
+    };
+
+    let (userinfo, host_and_port) = match authority.rsplit_once('@') {
+        Some((userinfo, host_and_port)) => (Some(userinfo), host_and_port),
+        None => (None, authority),
+    };
+    let (username, password) = match userinfo {
+        Some(userinfo) => match userinfo.split_once(':') {
+            Some((username, password)) => (username, Some(password)),
+            None => (userinfo, None),
+        },
+        None => ("", None),
+    };
+    let password = password.filter(|password| !password.is_empty());
+
+    let (host, port) = if host_and_port.starts_with('[') {
+        let invalid_host = || UrlError::InvalidHost(host_and_port.to_string());
+        let end = host_and_port.find(']').ok_or_else(invalid_host)?;
+        let (host, rest) = host_and_port.split_at(end + 1);
+        let address = host[1..host.len() - 1]
+            .parse::<Ipv6Addr>()
+            .map_err(|_| invalid_host())?;
+        let port = match rest {
+            "" => None,
+            rest => Some(rest.strip_prefix(':').ok_or_else(invalid_host)?),
+        };
+        (format!("[{address}]"), port)
+    } else {
+        let (host, port) = match host_and_port.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_and_port, None),
+        };
+        if host.is_empty() {
+            return Err(UrlError::MissingHost);
+        }
+        let host = domain_to_ascii(host)?;
+        if ends_in_a_number(&host) {
+            (parse_ipv4(&host)?.to_string(), port)
+        } else {
+            (host, port)
+        }
+    };
+    let port = match port {
+        Some(port) if !port.is_empty() => port
+            .parse()
+            .map_err(|_| UrlError::InvalidPort(port.to_string()))?,
+        _ if tls => 443,
+        _ => 80,
+    };
+
+    Ok(HttpUrl {
+        tls,
+        username: percent_encode(username, EncodeSet::Userinfo),
+        password: password.map(|password| percent_encode(password, EncodeSet::Userinfo)),
+        host,
+        port,
+        path: percent_encode(path, EncodeSet::Path),
+        query: query.map(|query| percent_encode(query, EncodeSet::Query)),
+        fragment: fragment.map(|fragment| percent_encode(fragment, EncodeSet::Fragment)),
+    })
+}
+
+/// Whether a host should be parsed as an IPv4 address rather than a domain, i.e. whether
+/// its last label is a number.
+fn ends_in_a_number(host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    let last = host.rsplit('.').next().unwrap_or(host);
+    let hex = last.strip_prefix("0x").or_else(|| last.strip_prefix("0X"));
+    !last.is_empty()
+        && (last.bytes().all(|byte| byte.is_ascii_digit())
+            || hex.is_some_and(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit())))
+}
+
+/// Parses IPv4 addresses in any of the forms browsers accept, such as `127.0.0.1`, `0x7f.1`
+/// or `2130706433`.
+fn parse_ipv4(host: &str) -> Result<Ipv4Addr, UrlError> {
+    let invalid = || UrlError::InvalidHost(host.to_string());
+    let mut parts: Vec<&str> = host.split('.').collect();
+    if parts.len() > 1 && parts.last() == Some(&"") {
+        parts.pop();
+    }
+    if parts.len() > 4 {
+        return Err(invalid());
+    }
+    let numbers = parts
+        .iter()
+        .map(|part| parse_ipv4_number(part))
+        .collect::<Option<Vec<u64>>>()
+        .ok_or_else(invalid)?;
+    let (last, rest) = numbers.split_last().ok_or_else(invalid)?;
+    if rest.iter().any(|&number| number > 255) || *last >= 256u64.pow(5 - numbers.len() as u32) {
+        return Err(invalid());
+    }
+    let address = rest
+        .iter()
+        .enumerate()
+        .fold(*last, |address, (i, &number)| {
+            address + number * 256u64.pow(3 - i as u32)
+        });
+    Ok(Ipv4Addr::from(address as u32))
+}
+
+fn parse_ipv4_number(part: &str) -> Option<u64> {
+    let (digits, radix) =
+        if let Some(hex) = part.strip_prefix("0x").or_else(|| part.strip_prefix("0X")) {
+            (hex, 16)
+        } else if part.len() > 1 && part.starts_with('0') {
+            (&part[1..], 8)
+        } else {
+            (part, 10)
+        };
+    if part.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, radix).ok()
+}
+
+/// Forgiving base64 as used by `data:` URLs: padding is optional and leftover bits are ignored.
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+        .with_decode_allow_trailing_bits(true),
+);
+
+/// Parses the part of a `data:` URL after the scheme, following RFC 2397 and the WHATWG
+/// Fetch standard's data URL processor.
+pub(crate) fn parse_data(url: &str) -> Result<DataUrl, UrlError> {
+    let (media_type, contents) = url.split_once(',').ok_or_else(|| {
+        UrlError::MalformedDataUrl("missing a comma before the contents".to_string())
+    })?;
+    let mut media_type = media_type.trim_ascii();
+    let mut data = percent_decode(contents);
+
+    let mut base64 = false;
+    if let Some(index) = media_type.len().checked_sub(b"base64".len()) {
+        if media_type.is_char_boundary(index) && media_type[index..].eq_ignore_ascii_case("base64")
+        {
+            if let Some(rest) = media_type[..index].trim_ascii_end().strip_suffix(';') {
+                base64 = true;
+                media_type = rest;
+            }
+        }
+    }
+    if base64 {
+        data.retain(|byte| !byte.is_ascii_whitespace());
+        data = BASE64
+            .decode(&data)
+            .map_err(|error| UrlError::MalformedDataUrl(format!("invalid base64: {error}")))?;
+    }
+
+    let media_type = if media_type.starts_with(';') {
+        MediaType::parse(&format!("text/plain{media_type}"))
+    } else {
+        MediaType::parse(media_type)
+    }
+    .unwrap_or_else(|| MediaType::new("text/plain").with_parameter("charset", "US-ASCII"));
+
+    Ok(DataUrl {
+        media_type,
+        base64,
+        data,
+    })
+}
+
+/// Lowercases and punycode-encodes a domain per UTS #46, after decoding any percent-encoded
+/// bytes.
+fn domain_to_ascii(host: &str) -> Result<String, UrlError> {
+    idna::domain_to_ascii_cow(&percent_decode(host), idna::AsciiDenyList::URL)
+        .ok()
+        .filter(|ascii_host| !ascii_host.is_empty())
+        .map(|ascii_host| ascii_host.into_owned())
+        .ok_or_else(|| UrlError::InvalidHost(host.to_string()))
+}
+
+fn has_scheme(reference: &str) -> bool {
+    let Some((scheme, _)) = reference.split_once(':') else {
+        return false;
+    };
+    let mut chars = scheme.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// Splits a path into its path, query and fragment parts.
+fn split_path(path: &str) -> (&str, Option<&str>, Option<&str>) {
+    let (path, fragment) = match path.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (path, None),
+    };
+    let (path, query) = match path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path, None),
+    };
+    (path, query, fragment)
+}
+
+/// Resolves a reference against a base path and query, returning the resulting path, query
+/// and fragment.
+fn resolve_path(
+    base_path: &str,
+    base_query: Option<&str>,
+    reference: &str,
+) -> (String, Option<String>, Option<String>) {
+    let (reference_path, reference_query, reference_fragment) = split_path(reference);
+
+    let (resolved, query) = if reference_path.is_empty() {
+        (base_path.to_string(), reference_query.or(base_query))
+    } else if reference_path.starts_with('/') {
+        (remove_dot_segments(reference_path), reference_query)
+    } else {
+        let merged = match base_path.rfind('/') {
+            Some(index) => format!("{}{}", &base_path[..=index], reference_path),
+            None => reference_path.to_string(),
+        };
+        (remove_dot_segments(&merged), reference_query)
+    };
+
+    (
+        resolved,
+        query.map(str::to_string),
+        reference_fragment.map(str::to_string),
+    )
+}
+
+fn remove_dot_segments(path: &str) -> String {
+    let absolute = path.starts_with('/');
+    let segments: Vec<&str> = path.strip_prefix('/').unwrap_or(path).split('/').collect();
+
+    let mut output = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        let is_last = i == segments.len() - 1;
+        match segment.to_ascii_lowercase().as_str() {
+            "." | "%2e" => {}
+            ".." | ".%2e" | "%2e." | "%2e%2e" => {
+                output.pop();
+            }
+            _ => {
+                output.push(*segment);
+                continue;
+            }
+        }
+        // A trailing dot segment still refers to a directory.
+        if is_last {
+            output.push("");
+        }
+    }
+
+    let joined = output.join("/");
+    if absolute {
+        format!("/{joined}")
+    } else {
+        joined
     }
 }
 
@@ -165,9 +716,13 @@ mod tests {
         let expected = Url {
             scheme: Scheme::Http(HttpUrl {
                 tls: false,
+                username: String::new(),
+                password: None,
                 host: "example.org".to_string(),
                 port: 80,
                 path: "/index.html".to_string(),
+                query: None,
+                fragment: None,
             }),
             view_source: false,
         };
@@ -182,9 +737,13 @@ mod tests {
         let expected = Url {
             scheme: Scheme::Http(HttpUrl {
                 tls: false,
+                username: String::new(),
+                password: None,
                 host: "example.org".to_string(),
                 port: 80,
                 path: "/".to_string(),
+                query: None,
+                fragment: None,
             }),
             view_source: false,
         };
@@ -196,9 +755,13 @@ mod tests {
         let expected = Url {
             scheme: Scheme::Http(HttpUrl {
                 tls: false,
+                username: String::new(),
+                password: None,
                 host: "example.org".to_string(),
                 port: 3000,
                 path: "/index.html".to_string(),
+                query: None,
+                fragment: None,
             }),
             view_source: false,
         };
@@ -208,14 +771,222 @@ mod tests {
         );
     }
 
+    #[test]
+    fn url_parse_http_with_ipv6_host() {
+        let expected = Url {
+            scheme: Scheme::Http(HttpUrl {
+                tls: false,
+                username: String::new(),
+                password: None,
+                host: "[::1]".to_string(),
+                port: 8080,
+                path: "/".to_string(),
+                query: None,
+                fragment: None,
+            }),
+            view_source: false,
+        };
+        assert_eq!(expected, Url::parse("http://[::1]:8080/").unwrap());
+    }
+
+    #[test]
+    fn url_parse_http_with_ipv6_host_and_default_port() {
+        let Scheme::Http(http_url) = Url::parse("https://[2001:db8::1]/index.html")
+            .unwrap()
+            .scheme
+        else {
+            panic!("expected an HTTP URL");
+        };
+        assert_eq!(http_url.host, "[2001:db8::1]");
+        assert_eq!(http_url.connect_host(), "2001:db8::1");
+        assert_eq!(http_url.port, 443);
+    }
+
+    #[test]
+    fn url_parse_http_with_invalid_ipv6_host() {
+        for input in [
+            "http://[::1/",
+            "http://[not-an-address]/",
+            "http://[::1]junk/",
+        ] {
+            assert!(
+                matches!(Url::parse(input), Err(UrlError::InvalidHost(_))),
+                "parsing {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn url_parse_http_with_ipv6_host_is_normalized() {
+        let Scheme::Http(http_url) = Url::parse("http://[0:0::0:1]/").unwrap().scheme else {
+            panic!("expected an HTTP URL");
+        };
+        assert_eq!(http_url.host, "[::1]");
+    }
+
+    #[test]
+    fn url_parse_http_lowercases_host() {
+        let Scheme::Http(http_url) = Url::parse("http://Example.ORG/Index.html").unwrap().scheme
+        else {
+            panic!("expected an HTTP URL");
+        };
+        assert_eq!(http_url.host, "example.org");
+        assert_eq!(http_url.path, "/Index.html");
+    }
+
+    #[test]
+    fn url_parse_http_with_internationalized_host() {
+        let Scheme::Http(http_url) = Url::parse("https://Bücher.example/").unwrap().scheme else {
+            panic!("expected an HTTP URL");
+        };
+        assert_eq!(http_url.host, "xn--bcher-kva.example");
+        assert_eq!(http_url.unicode_host(), "bücher.example");
+    }
+
+    #[test]
+    fn url_parse_http_with_uts46_mapped_host() {
+        let cases = [
+            ("http://faß.de/", "xn--fa-hia.de"),
+            ("http://ＥＸＡＭＰＬＥ．com/", "example.com"),
+            ("http://%E4%BE%8B%E3%81%88.jp/", "xn--r8jz45g.jp"),
+            ("http://xn--r8jz45g.jp/", "xn--r8jz45g.jp"),
+        ];
+        for (input, expected) in cases {
+            let Scheme::Http(http_url) = Url::parse(input).unwrap().scheme else {
+                panic!("expected an HTTP URL");
+            };
+            assert_eq!(http_url.host, expected, "parsing {input:?}");
+        }
+    }
+
+    #[test]
+    fn url_parse_http_with_invalid_host() {
+        for input in [
+            "http://exa mple.org/",
+            "http://xn--a.example/",
+            "http://exa%00mple.org/",
+            "http://256.0.0.1/",
+        ] {
+            assert!(
+                matches!(Url::parse(input), Err(UrlError::InvalidHost(_))),
+                "parsing {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn url_parse_http_with_userinfo() {
+        let expected = Url {
+            scheme: Scheme::Http(HttpUrl {
+                tls: false,
+                username: "user".to_string(),
+                password: Some("pa%3Ass".to_string()),
+                host: "example.org".to_string(),
+                port: 3000,
+                path: "/private".to_string(),
+                query: None,
+                fragment: None,
+            }),
+            view_source: false,
+        };
+        assert_eq!(
+            expected,
+            Url::parse("http://user:pa:ss@example.org:3000/private").unwrap()
+        );
+    }
+
+    #[test]
+    fn url_parse_http_with_username_only() {
+        let Scheme::Http(http_url) = Url::parse("http://me@[::1]/").unwrap().scheme else {
+            panic!("expected an HTTP URL");
+        };
+        assert_eq!(http_url.username, "me");
+        assert_eq!(http_url.password, None);
+        assert_eq!(http_url.host, "[::1]");
+    }
+
+    #[test]
+    fn url_parse_http_with_empty_host() {
+        assert_eq!(Url::parse("http://"), Err(UrlError::MissingHost));
+        assert_eq!(
+            Url::parse("http://:8080/index.html"),
+            Err(UrlError::MissingHost)
+        );
+        assert_eq!(Url::parse("http://user@/"), Err(UrlError::MissingHost));
+    }
+
+    #[test]
+    fn url_parse_unsupported_scheme() {
+        assert_eq!(
+            Url::parse("FTP://example.org:21/"),
+            Err(UrlError::UnsupportedScheme("ftp".to_string()))
+        );
+    }
+
+    #[test]
+    fn url_parse_about_pages() {
+        for page in [
+            "blank",
+            "version",
+            "cache",
+            "connections",
+            "history",
+            "config",
+        ] {
+            let input = format!("about:{page}");
+            let url = Url::parse(&input.to_uppercase()).unwrap();
+            assert!(matches!(url.scheme, Scheme::Builtin(_)));
+            assert_eq!(url.to_string(), input);
+        }
+    }
+
+    #[test]
+    fn url_parse_missing_scheme() {
+        for input in ["example.org", "how do I bake bread?", "/index.html", ""] {
+            assert_eq!(
+                Url::parse(input),
+                Err(UrlError::MissingScheme),
+                "parsing {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn url_parse_invalid_port() {
+        assert_eq!(
+            Url::parse("http://example.org:99999/"),
+            Err(UrlError::InvalidPort("99999".to_string()))
+        );
+        assert_eq!(
+            Url::parse("http://example.org:http/"),
+            Err(UrlError::InvalidPort("http".to_string()))
+        );
+    }
+
+    #[test]
+    fn url_error_display() {
+        assert_eq!(
+            UrlError::UnsupportedScheme("ftp".to_string()).to_string(),
+            "Unsupported URL scheme 'ftp'"
+        );
+        assert_eq!(
+            UrlError::InvalidPort("x".to_string()).to_string(),
+            "Invalid port 'x'"
+        );
+    }
+
     #[test]
     fn url_parse_http_with_view_source() {
         let expected = Url {
             scheme: Scheme::Http(HttpUrl {
                 tls: false,
+                username: String::new(),
+                password: None,
                 host: "example.org".to_string(),
                 port: 80,
                 path: "/index.html".to_string(),
+                query: None,
+                fragment: None,
             }),
             view_source: true,
         };
@@ -230,9 +1001,13 @@ mod tests {
         let expected = Url {
             scheme: Scheme::Http(HttpUrl {
                 tls: true,
+                username: String::new(),
+                password: None,
                 host: "example.org".to_string(),
                 port: 443,
                 path: "/index.html".to_string(),
+                query: None,
+                fragment: None,
             }),
             view_source: false,
         };
@@ -242,6 +1017,202 @@ mod tests {
         );
     }
 
+    #[test]
+    fn url_parse_http_with_query_and_fragment() {
+        let expected = Url {
+            scheme: Scheme::Http(HttpUrl {
+                tls: false,
+                username: String::new(),
+                password: None,
+                host: "example.org".to_string(),
+                port: 80,
+                path: "/search".to_string(),
+                query: Some("q=1&lang=en".to_string()),
+                fragment: Some("results".to_string()),
+            }),
+            view_source: false,
+        };
+        assert_eq!(
+            expected,
+            Url::parse("http://example.org/search?q=1&lang=en#results").unwrap()
+        );
+    }
+
+    #[test]
+    fn url_parse_http_with_query_and_no_path() {
+        let expected = Url {
+            scheme: Scheme::Http(HttpUrl {
+                tls: false,
+                username: String::new(),
+                password: None,
+                host: "example.org".to_string(),
+                port: 8080,
+                path: "/".to_string(),
+                query: Some("a=b".to_string()),
+                fragment: None,
+            }),
+            view_source: false,
+        };
+        assert_eq!(expected, Url::parse("http://example.org:8080?a=b").unwrap());
+    }
+
+    #[test]
+    fn url_parse_http_with_fragment_containing_question_mark() {
+        let url = Url::parse("http://example.org/#what?").unwrap();
+        let Scheme::Http(http_url) = url.scheme else {
+            panic!("expected an HTTP URL");
+        };
+        assert_eq!(http_url.path, "/");
+        assert_eq!(http_url.query, None);
+        assert_eq!(http_url.fragment, Some("what?".to_string()));
+    }
+
+    #[test]
+    fn http_url_request_target_excludes_fragment() {
+        let http_url = HttpUrl {
+            tls: false,
+            username: String::new(),
+            password: None,
+            host: "example.org".to_string(),
+            port: 80,
+            path: "/docs/page.html".to_string(),
+            query: Some("v=2".to_string()),
+            fragment: Some("section".to_string()),
+        };
+        assert_eq!(http_url.request_target(), "/docs/page.html?v=2");
+    }
+
+    #[test]
+    fn http_url_request_target_is_percent_encoded() {
+        let http_url = HttpUrl {
+            tls: false,
+            username: String::new(),
+            password: None,
+            host: "example.org".to_string(),
+            port: 80,
+            path: "/My Docs/café.html".to_string(),
+            query: Some("q=a b".to_string()),
+            fragment: None,
+        };
+        assert_eq!(
+            http_url.request_target(),
+            "/My%20Docs/caf%C3%A9.html?q=a%20b"
+        );
+    }
+
+    #[test]
+    fn http_url_request_target_does_not_double_encode() {
+        let Scheme::Http(http_url) = Url::parse("http://example.org/a%20b?c%26d").unwrap().scheme
+        else {
+            panic!("expected an HTTP URL");
+        };
+        assert_eq!(http_url.request_target(), "/a%20b?c%26d");
+    }
+
+    #[test]
+    fn file_url_to_file_path_is_decoded() {
+        let Scheme::File(file_url) = Url::parse("file:///tmp/My%20Page.html#top").unwrap().scheme
+        else {
+            panic!("expected a file URL");
+        };
+        assert_eq!(file_url.to_file_path(), PathBuf::from("/tmp/My Page.html"));
+    }
+
+    #[test]
+    fn url_parse_data_percent_decodes_contents() {
+        let Scheme::Data(data_url) = Url::parse("data:text/html,%3Cb%3EHi%3C/b%3E")
+            .unwrap()
+            .scheme
+        else {
+            panic!("expected a data URL");
+        };
+        assert_eq!(data_url.data, b"<b>Hi</b>");
+    }
+
+    #[test]
+    fn url_parse_data_base64() {
+        let expected = Url {
+            scheme: Scheme::Data(DataUrl {
+                media_type: MediaType::new("image/png"),
+                base64: true,
+                data: vec![0x89, b'P', b'N', b'G'],
+            }),
+            view_source: false,
+        };
+        assert_eq!(
+            expected,
+            Url::parse("data:image/png;base64,iVBORw==").unwrap()
+        );
+    }
+
+    #[test]
+    fn url_parse_data_base64_is_forgiving() {
+        let cases = [
+            "data:;base64,SGVsbG8=",
+            "data:;BASE64,SGVsbG8",
+            "data:text/plain ; base64 ,SGVs%20bG8=",
+            "data:;base64,S G V s\nb G 8",
+        ];
+        for input in cases {
+            let Scheme::Data(data_url) = Url::parse(input).unwrap().scheme else {
+                panic!("expected a data URL");
+            };
+            assert_eq!(data_url.data, b"Hello", "parsing {input:?}");
+        }
+    }
+
+    #[test]
+    fn url_parse_data_invalid_base64() {
+        assert!(matches!(
+            Url::parse("data:;base64,S"),
+            Err(UrlError::MalformedDataUrl(_))
+        ));
+        assert!(matches!(
+            Url::parse("data:;base64,SGV*bG8="),
+            Err(UrlError::MalformedDataUrl(_))
+        ));
+    }
+
+    #[test]
+    fn url_parse_data_with_parameters() {
+        let Scheme::Data(data_url) = Url::parse("data:Text/HTML;Charset=UTF-8,caf%C3%A9")
+            .unwrap()
+            .scheme
+        else {
+            panic!("expected a data URL");
+        };
+        assert_eq!(data_url.media_type.essence, "text/html");
+        assert_eq!(data_url.media_type.parameter("charset"), Some("UTF-8"));
+        assert!(!data_url.base64);
+        assert_eq!(data_url.data, "café".as_bytes());
+    }
+
+    #[test]
+    fn url_parse_data_default_media_type() {
+        for input in ["data:,Hello", "data:invalid,Hello"] {
+            let Scheme::Data(data_url) = Url::parse(input).unwrap().scheme else {
+                panic!("expected a data URL");
+            };
+            assert_eq!(
+                data_url.media_type.to_string(),
+                "text/plain;charset=US-ASCII",
+                "parsing {input:?}"
+            );
+        }
+        let Scheme::Data(data_url) = Url::parse("data:;charset=utf-8,Hello").unwrap().scheme else {
+            panic!("expected a data URL");
+        };
+        assert_eq!(data_url.media_type.to_string(), "text/plain;charset=utf-8");
+    }
+
+    #[test]
+    fn url_parse_data_without_comma() {
+        assert!(matches!(
+            Url::parse("data:text/html"),
+            Err(UrlError::MalformedDataUrl(_))
+        ));
+    }
+
     #[test]
     fn url_parse_file() {
         let expected = Url {
@@ -257,8 +1228,9 @@ mod tests {
     fn url_parse_data() {
         let expected = Url {
             scheme: Scheme::Data(DataUrl {
-                content_type: "text/html".to_string(),
-                contents: "Hello world!".to_string(),
+                media_type: MediaType::new("text/html"),
+                base64: false,
+                data: b"Hello world!".to_vec(),
             }),
             view_source: false,
         };
@@ -270,13 +1242,17 @@ mod tests {
         let url = Url {
             scheme: Scheme::Http(HttpUrl {
                 tls: false,
+                username: String::new(),
+                password: None,
                 host: "example.org".to_string(),
                 port: 80,
                 path: "/index.html".to_string(),
+                query: None,
+                fragment: None,
             }),
             view_source: false,
         };
-        assert_eq!(format!("{}", url), "http://example.org:80/index.html");
+        assert_eq!(format!("{}", url), "http://example.org/index.html");
     }
 
     #[test]
@@ -284,29 +1260,70 @@ mod tests {
         let url = Url {
             scheme: Scheme::Http(HttpUrl {
                 tls: true,
+                username: String::new(),
+                password: None,
                 host: "example.org".to_string(),
                 port: 80,
                 path: "/index.html".to_string(),
+                query: None,
+                fragment: None,
             }),
             view_source: false,
         };
         assert_eq!(format!("{}", url), "https://example.org:80/index.html");
     }
 
+    #[test]
+    fn display_http_url_with_query_and_fragment() {
+        let url = Url {
+            scheme: Scheme::Http(HttpUrl {
+                tls: false,
+                username: String::new(),
+                password: None,
+                host: "example.org".to_string(),
+                port: 80,
+                path: "/search".to_string(),
+                query: Some("q=1".to_string()),
+                fragment: Some("top".to_string()),
+            }),
+            view_source: false,
+        };
+        assert_eq!(format!("{}", url), "http://example.org/search?q=1#top");
+    }
+
+    #[test]
+    fn display_http_url_is_percent_encoded() {
+        let url = Url::parse("http://example.org/My Page.html?q=a b#see also").unwrap();
+        assert_eq!(
+            format!("{}", url),
+            "http://example.org/My%20Page.html?q=a%20b#see%20also"
+        );
+    }
+
+    #[test]
+    fn display_http_url_with_ipv6_host_and_userinfo() {
+        let url = Url::parse("http://user:p@ss@[::1]:8080/").unwrap();
+        assert_eq!(format!("{}", url), "http://user:p%40ss@[::1]:8080/");
+    }
+
     #[test]
     fn display_view_source_url() {
         let url = Url {
             scheme: Scheme::Http(HttpUrl {
                 tls: false,
+                username: String::new(),
+                password: None,
                 host: "example.org".to_string(),
                 port: 80,
                 path: "/index.html".to_string(),
+                query: None,
+                fragment: None,
             }),
             view_source: true,
         };
         assert_eq!(
             format!("{}", url),
-            "view-source:http://example.org:80/index.html"
+            "view-source:http://example.org/index.html"
         );
     }
 
@@ -325,11 +1342,218 @@ mod tests {
     fn display_data_url() {
         let url = Url {
             scheme: Scheme::Data(DataUrl {
-                content_type: "text/html".to_string(),
-                contents: "<b>Hello world!</b>".to_string(),
+                media_type: MediaType::new("text/html"),
+                base64: false,
+                data: b"<b>Hello world!</b>".to_vec(),
             }),
             view_source: false,
         };
         assert_eq!(format!("{}", url), "data:text/html,<b>Hello world!</b>");
     }
+
+    #[test]
+    fn display_data_url_round_trips() {
+        for input in [
+            "data:text/plain;charset=utf-8,100%25 caf%C3%A9%23",
+            "data:image/png;base64,iVBORw==",
+        ] {
+            assert_eq!(Url::parse(input).unwrap().to_string(), input);
+        }
+    }
+
+    #[test]
+    fn url_resolve_rfc3986_examples() -> color_eyre::Result<()> {
+        let base = Url::parse("http://a/b/c/d;p?q")?;
+        let cases = [
+            ("g", "http://a/b/c/g"),
+            ("./g", "http://a/b/c/g"),
+            ("g/", "http://a/b/c/g/"),
+            ("/g", "http://a/g"),
+            ("//g", "http://g/"),
+            ("?y", "http://a/b/c/d;p?y"),
+            ("g?y", "http://a/b/c/g?y"),
+            ("#s", "http://a/b/c/d;p?q#s"),
+            ("g#s", "http://a/b/c/g#s"),
+            ("g?y#s", "http://a/b/c/g?y#s"),
+            (";x", "http://a/b/c/;x"),
+            ("g;x", "http://a/b/c/g;x"),
+            ("g;x?y#s", "http://a/b/c/g;x?y#s"),
+            ("", "http://a/b/c/d;p?q"),
+            (".", "http://a/b/c/"),
+            ("./", "http://a/b/c/"),
+            ("..", "http://a/b/"),
+            ("../", "http://a/b/"),
+            ("../g", "http://a/b/g"),
+            ("../..", "http://a/"),
+            ("../../", "http://a/"),
+            ("../../g", "http://a/g"),
+            ("../../../g", "http://a/g"),
+            ("../../../../g", "http://a/g"),
+            ("/./g", "http://a/g"),
+            ("/../g", "http://a/g"),
+            ("g.", "http://a/b/c/g."),
+            (".g", "http://a/b/c/.g"),
+            ("g..", "http://a/b/c/g.."),
+            ("..g", "http://a/b/c/..g"),
+            ("./../g", "http://a/b/g"),
+            ("./g/.", "http://a/b/c/g/"),
+            ("g/./h", "http://a/b/c/g/h"),
+            ("g/../h", "http://a/b/c/h"),
+            ("g;x=1/./y", "http://a/b/c/g;x=1/y"),
+            ("g;x=1/../y", "http://a/b/c/y"),
+            ("g?y/./x", "http://a/b/c/g?y/./x"),
+            ("g?y/../x", "http://a/b/c/g?y/../x"),
+            ("g#s/./x", "http://a/b/c/g#s/./x"),
+            ("g#s/../x", "http://a/b/c/g#s/../x"),
+            (
+                "https://example.org/index.html",
+                "https://example.org/index.html",
+            ),
+        ];
+        for (reference, expected) in cases {
+            assert_eq!(
+                expected,
+                base.resolve(reference)?.to_string(),
+                "resolving {reference:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn url_resolve_scheme_relative_keeps_tls() -> color_eyre::Result<()> {
+        let base = Url::parse("https://example.org/a/b.html")?;
+        assert_eq!(
+            "https://cdn.example.org/style.css",
+            base.resolve("//cdn.example.org/style.css")?.to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn url_resolve_relative_file_path() -> color_eyre::Result<()> {
+        let base = Url::parse("file://examples/welcome.html")?;
+        assert_eq!(
+            "file://examples/other.html",
+            base.resolve("other.html")?.to_string()
+        );
+        assert_eq!(
+            "file://other.html",
+            base.resolve("../other.html")?.to_string()
+        );
+        assert_eq!("file:///etc/hosts", base.resolve("/etc/hosts")?.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn url_resolve_does_not_inherit_view_source() -> color_eyre::Result<()> {
+        let base = Url::parse("view-source:http://example.org/a/b.html")?;
+        assert_eq!(
+            "http://example.org/a/c.html",
+            base.resolve("c.html")?.to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn url_resolve_against_data_url_fails() -> color_eyre::Result<()> {
+        let base = Url::parse("data:text/html,Hello world!")?;
+        assert_eq!(
+            base.resolve("index.html"),
+            Err(UrlError::CannotResolveRelative("index.html".to_string()))
+        );
+        assert_eq!("about:blank", base.resolve("about:blank")?.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn url_parse_is_canonical() {
+        let cases = [
+            ("HTTP://Example.ORG:80/a/./b/../c", "http://example.org/a/c"),
+            (
+                "  https://example.org:443\t/index.html\n ",
+                "https://example.org/index.html",
+            ),
+            (
+                "View-Source:HTTPS://example.org/",
+                "view-source:https://example.org/",
+            ),
+            (
+                "http://example.org/%2e%2E/a/%2E/b",
+                "http://example.org/a/b",
+            ),
+            (
+                "http:\\\\example.org\\a\\b?c\\d",
+                "http://example.org/a/b?c\\d",
+            ),
+            ("http://0x7f.1/", "http://127.0.0.1/"),
+            ("http://2130706433/", "http://127.0.0.1/"),
+            ("http://user:@example.org/", "http://user@example.org/"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                Url::parse(input).unwrap().to_string(),
+                expected,
+                "parsing {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn url_parse_invalid_ipv4() {
+        assert!(Url::parse("http://256.0.0.1/").is_err());
+        assert!(Url::parse("http://1.2.3.4.5/").is_err());
+        assert!(Url::parse("http://example.0xg/").is_ok());
+        assert!(Url::parse("http://example.09/").is_err());
+    }
+
+    #[test]
+    fn http_url_authority_omits_default_port() {
+        let Scheme::Http(http_url) = Url::parse("https://example.org:443/").unwrap().scheme else {
+            panic!("expected an HTTP URL");
+        };
+        assert_eq!(http_url.authority(), "example.org");
+        let Scheme::Http(http_url) = Url::parse("https://example.org:8443/").unwrap().scheme else {
+            panic!("expected an HTTP URL");
+        };
+        assert_eq!(http_url.authority(), "example.org:8443");
+    }
+
+    /// Runs the vendored subset of the WHATWG URL test data that applies to `http` and
+    /// `https` URLs.
+    #[test]
+    fn whatwg_url_test_data() {
+        let cases: serde_json::Value =
+            serde_json::from_str(include_str!("url/urltestdata.json")).unwrap();
+        let mut failures = Vec::new();
+        for case in cases.as_array().unwrap() {
+            // Plain strings are comments.
+            if case.is_string() {
+                continue;
+            }
+            let input = case["input"].as_str().unwrap();
+            let result = match case["base"].as_str() {
+                Some(base) => Url::parse(base).and_then(|base| base.resolve(input)),
+                None => Url::parse(input),
+            };
+            let result = result.map(|url| url.to_string());
+            let passed = match (&result, case["href"].as_str()) {
+                (Ok(href), Some(expected)) => href == expected,
+                (Err(_), None) => true,
+                _ => false,
+            };
+            if !passed {
+                failures.push(format!(
+                    "{input:?} (base {}): expected {}, got {result:?}",
+                    case["base"], case["href"]
+                ));
+            }
+        }
+        assert!(
+            failures.is_empty(),
+            "{} cases failed:\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
+    }
 }
@@ -0,0 +1,38 @@
+use std::fmt::Display;
+
+/// Why a string couldn't be parsed or resolved as a [`Url`](crate::Url).
+#[derive(Debug, Clone, PartialEq)]
+pub enum UrlError {
+    /// The input has no `scheme:` prefix, so it is probably not a URL at all.
+    MissingScheme,
+    UnsupportedScheme(String),
+    /// An `about:` URL naming a page the browser doesn't have.
+    UnknownAboutPage(String),
+    MissingHost,
+    InvalidHost(String),
+    InvalidPort(String),
+    /// A `data:` URL without a comma, or with an invalid base64 payload.
+    MalformedDataUrl(String),
+    /// A relative reference was resolved against a URL that can't have relative URLs, such as
+    /// a `data:` or `about:` URL.
+    CannotResolveRelative(String),
+}
+
+impl Display for UrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UrlError::MissingScheme => write!(f, "URL must have a scheme"),
+            UrlError::UnsupportedScheme(scheme) => write!(f, "Unsupported URL scheme '{scheme}'"),
+            UrlError::UnknownAboutPage(page) => write!(f, "Unknown page 'about:{page}'"),
+            UrlError::MissingHost => write!(f, "URL must have a host"),
+            UrlError::InvalidHost(host) => write!(f, "Invalid host '{host}'"),
+            UrlError::InvalidPort(port) => write!(f, "Invalid port '{port}'"),
+            UrlError::MalformedDataUrl(reason) => write!(f, "Malformed data URL: {reason}"),
+            UrlError::CannotResolveRelative(reference) => {
+                write!(f, "Cannot resolve relative URL '{reference}'")
+            }
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
@@ -0,0 +1,161 @@
+/// Sets of bytes that must be percent-encoded in each part of a URL, as defined by the WHATWG
+/// URL standard. Apart from `Opaque`, none of them include `%`, so already-encoded input is
+/// left as it is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EncodeSet {
+    /// For re-encoding already decoded contents, such as the payload of a `data:` URL.
+    Opaque,
+    Fragment,
+    /// The query set for special schemes such as `http` and `https`.
+    Query,
+    Path,
+    Userinfo,
+}
+
+impl EncodeSet {
+    pub fn contains(self, byte: u8) -> bool {
+        let is_c0_control = !(0x20..0x7f).contains(&byte);
+        match self {
+            EncodeSet::Opaque => is_c0_control || b"%#".contains(&byte),
+            EncodeSet::Fragment => is_c0_control || b" \"<>`".contains(&byte),
+            EncodeSet::Query => is_c0_control || b" \"#<>'".contains(&byte),
+            EncodeSet::Path => EncodeSet::Query.contains(byte) || b"?^`{}".contains(&byte),
+            EncodeSet::Userinfo => EncodeSet::Path.contains(byte) || b"/:;=@[\\]|".contains(&byte),
+        }
+    }
+}
+
+pub fn percent_encode(input: impl AsRef<[u8]>, set: EncodeSet) -> String {
+    let input = input.as_ref();
+    let mut out = String::with_capacity(input.len());
+    for &byte in input {
+        if set.contains(byte) {
+            out.push_str(&format!("%{byte:02X}"));
+        } else {
+            out.push(byte as char);
+        }
+    }
+    out
+}
+
+/// Decodes `%XX` sequences into raw bytes. Malformed sequences are passed through unchanged.
+pub fn percent_decode(input: &str) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3).and_then(|hex| {
+                std::str::from_utf8(hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            });
+            if let Some(byte) = hex {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_leaves_unreserved_characters_alone() {
+        assert_eq!(percent_encode("AZaz09-._~", EncodeSet::Path), "AZaz09-._~");
+    }
+
+    #[test]
+    fn encode_non_ascii_as_utf8_bytes() {
+        assert_eq!(percent_encode("/café", EncodeSet::Path), "/caf%C3%A9");
+    }
+
+    #[test]
+    fn encode_path_keeps_separators_and_existing_escapes() {
+        assert_eq!(
+            percent_encode("/My Docs/100%25 done?.html", EncodeSet::Path),
+            "/My%20Docs/100%25%20done%3F.html"
+        );
+    }
+
+    #[test]
+    fn encode_query_keeps_ampersands_and_equals() {
+        assert_eq!(
+            percent_encode("q=a b&lang=en#x", EncodeSet::Query),
+            "q=a%20b&lang=en%23x"
+        );
+    }
+
+    #[test]
+    fn encode_fragment_keeps_hash_and_question_mark() {
+        assert_eq!(percent_encode("a b#c?d", EncodeSet::Fragment), "a%20b#c?d");
+    }
+
+    #[test]
+    fn encode_userinfo_escapes_delimiters() {
+        assert_eq!(
+            percent_encode("a:b@c/d", EncodeSet::Userinfo),
+            "a%3Ab%40c%2Fd"
+        );
+    }
+
+    #[test]
+    fn encode_opaque_escapes_percent_signs() {
+        assert_eq!(
+            percent_encode(b"100% <b>\xFF</b>#", EncodeSet::Opaque),
+            "100%25 <b>%FF</b>%23"
+        );
+    }
+
+    #[test]
+    fn decode_escapes() {
+        assert_eq!(percent_decode("My%20Page.html"), b"My Page.html");
+        assert_eq!(percent_decode("caf%c3%a9"), "café".as_bytes());
+    }
+
+    #[test]
+    fn decode_passes_through_malformed_escapes() {
+        assert_eq!(percent_decode("100%"), b"100%");
+        assert_eq!(percent_decode("%4"), b"%4");
+        assert_eq!(percent_decode("%zz%41"), b"%zzA");
+    }
+
+    #[test]
+    fn decode_does_not_require_valid_utf8() {
+        assert_eq!(percent_decode("%FF%FE"), vec![0xFF, 0xFE]);
+    }
+
+    #[test]
+    fn opaque_round_trip() {
+        let input: Vec<u8> = (0..=255).collect();
+        let encoded = percent_encode(&input, EncodeSet::Opaque);
+        assert!(encoded.is_ascii(), "{encoded:?} should be ASCII");
+        assert_eq!(percent_decode(&encoded), input);
+    }
+
+    #[test]
+    fn round_trip() {
+        for input in [
+            "",
+            "hello world",
+            "a/b?c#d&e=f+g",
+            "user:p@ss[word]",
+            "naïve café ☕",
+            "\u{0}\t\r\n\u{7f}",
+        ] {
+            let encoded = percent_encode(input, EncodeSet::Path);
+            assert!(encoded.is_ascii(), "{encoded:?} should be ASCII");
+            assert_eq!(
+                percent_decode(&encoded),
+                input.as_bytes(),
+                "round-tripping {input:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::{collections::HashSet, sync::OnceLock};
+
+/// A snapshot of <https://publicsuffix.org/list/public_suffix_list.dat>. Refresh it by
+/// downloading the file again over the top of this one.
+const PUBLIC_SUFFIX_LIST: &str = include_str!("public_suffix_list.dat");
+
+struct Rules {
+    /// Normal rules such as `co.uk`.
+    suffixes: HashSet<String>,
+    /// Wildcard rules with the leading `*.` removed, so `*.ck` is stored as `ck`.
+    wildcards: HashSet<String>,
+    /// Exception rules with the leading `!` removed.
+    exceptions: HashSet<String>,
+}
+
+fn rules() -> &'static Rules {
+    static RULES: OnceLock<Rules> = OnceLock::new();
+    RULES.get_or_init(|| {
+        let mut rules = Rules {
+            suffixes: HashSet::new(),
+            wildcards: HashSet::new(),
+            exceptions: HashSet::new(),
+        };
+        for line in PUBLIC_SUFFIX_LIST.lines() {
+            let Some(rule) = line.split_whitespace().next() else {
+                continue;
+            };
+            if rule.starts_with("//") {
+                continue;
+            }
+            // Hosts are stored in their punycode form, so the rules need to be too.
+            let (set, rule) = if let Some(rule) = rule.strip_prefix("*.") {
+                (&mut rules.wildcards, rule)
+            } else if let Some(rule) = rule.strip_prefix('!') {
+                (&mut rules.exceptions, rule)
+            } else {
+                (&mut rules.suffixes, rule)
+            };
+            if let Ok(rule) = idna::domain_to_ascii(rule) {
+                set.insert(rule);
+            }
+        }
+        rules
+    })
+}
+
+/// The public suffix of an ASCII domain, e.g. `co.uk` for `www.example.co.uk`. A domain that
+/// isn't covered by any rule is treated as having its last label as the public suffix.
+pub fn public_suffix(domain: &str) -> &str {
+    let rules = rules();
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    let lowercase = domain.to_ascii_lowercase();
+
+    let mut start = 0;
+    loop {
+        let candidate = &lowercase[start..];
+        let parent_start = candidate.find('.').map(|dot| start + dot + 1);
+        if rules.exceptions.contains(candidate) {
+            // An exception rule means the domain itself is registrable, so the suffix is
+            // everything after its first label.
+            return &domain[parent_start.unwrap_or(domain.len())..];
+        }
+        if rules.suffixes.contains(candidate)
+            || parent_start.is_some_and(|parent| rules.wildcards.contains(&lowercase[parent..]))
+        {
+            return &domain[start..];
+        }
+        match parent_start {
+            Some(parent) => start = parent,
+            None => return &domain[start..],
+        }
+    }
+}
+
+/// The public suffix plus one more label, e.g. `example.co.uk` for `www.example.co.uk`. Returns
+/// `None` if the domain is itself a public suffix.
+pub fn registrable_domain(domain: &str) -> Option<&str> {
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    let suffix = public_suffix(domain);
+    let before_suffix = domain.strip_suffix(suffix)?.strip_suffix('.')?;
+    let label_start = before_suffix.rfind('.').map_or(0, |dot| dot + 1);
+    Some(&domain[label_start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_suffix_of_common_domains() {
+        assert_eq!(public_suffix("example.com"), "com");
+        assert_eq!(public_suffix("www.example.co.uk"), "co.uk");
+        assert_eq!(public_suffix("co.uk"), "co.uk");
+        assert_eq!(public_suffix("localhost"), "localhost");
+        assert_eq!(public_suffix("example.unknowntld"), "unknowntld");
+    }
+
+    #[test]
+    fn public_suffix_with_wildcard_and_exception_rules() {
+        assert_eq!(public_suffix("a.b.ck"), "b.ck");
+        assert_eq!(public_suffix("www.ck"), "ck");
+        assert_eq!(public_suffix("foo.www.ck"), "ck");
+    }
+
+    #[test]
+    fn public_suffix_of_internationalized_domain() {
+        // 公司.cn is in the list in its Unicode form.
+        assert_eq!(public_suffix("example.xn--55qx5d.cn"), "xn--55qx5d.cn");
+    }
+
+    #[test]
+    fn registrable_domains() {
+        assert_eq!(registrable_domain("example.com"), Some("example.com"));
+        assert_eq!(registrable_domain("a.b.example.com"), Some("example.com"));
+        assert_eq!(
+            registrable_domain("www.example.co.uk"),
+            Some("example.co.uk")
+        );
+        assert_eq!(registrable_domain("Foo.GitHub.io"), Some("Foo.GitHub.io"));
+        assert_eq!(registrable_domain("example.com."), Some("example.com"));
+        assert_eq!(registrable_domain("co.uk"), None);
+        assert_eq!(registrable_domain("github.io"), None);
+        assert_eq!(registrable_domain("localhost"), None);
+    }
+}